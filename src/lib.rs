@@ -1,13 +1,187 @@
-use anyhow::{Context, Result};
-use bollard::{Docker, container::{CreateContainerOptions, Config, StartContainerOptions}, network::{CreateNetworkOptions, ConnectNetworkOptions}, models::{Ipam, IpamConfig, HostConfig, EndpointSettings, PortBinding}};
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use bollard::{Docker, container::{CreateContainerOptions, Config, LogOutput, LogsOptions, RemoveContainerOptions, StartContainerOptions, StatsOptions, StopContainerOptions, WaitContainerOptions}, exec::{CreateExecOptions, StartExecResults}, image::CreateImageOptions, network::{CreateNetworkOptions, ConnectNetworkOptions, DisconnectNetworkOptions, InspectNetworkOptions}, models::{Ipam, IpamConfig, HostConfig, HostConfigLogConfig, ResourcesUlimits, EndpointSettings, PortBinding}};
+use futures_util::StreamExt;
+use reqwest::{Client, Method, StatusCode, header::HeaderMap};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_json::json;
-use std::collections::HashMap;
-use std::time::Duration;
+use std::collections::{BTreeSet, HashMap};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{Semaphore, SemaphorePermit};
 use tokio::time::sleep;
+pub use tokio_util::sync::CancellationToken;
 use tracing::{info, warn, debug};
 
+pub type Result<T, E = WakuError> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub enum WakuError {
+    DockerConnect(bollard::errors::Error),
+    Docker(bollard::errors::Error),
+    ContainerStart(String),
+    NodeNotReady(String),
+    HttpRequest(reqwest::Error),
+    ParseResponse(String),
+    Timeout(String),
+    Io(std::io::Error),
+    Failed(String),
+    Config(String),
+    Context { message: String, source: Box<WakuError> },
+}
+
+impl WakuError {
+    pub fn context(self, message: impl std::fmt::Display) -> Self {
+        WakuError::Context { message: message.to_string(), source: Box::new(self) }
+    }
+
+    // The underlying failure with any context layers stripped, for matching on the kind
+    pub fn root(&self) -> &WakuError {
+        match self {
+            WakuError::Context { source, .. } => source.root(),
+            other => other,
+        }
+    }
+}
+
+impl std::fmt::Display for WakuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WakuError::DockerConnect(e) => write!(f, "Failed to connect to Docker daemon: {}", e),
+            WakuError::Docker(e) => write!(f, "Docker API error: {}", e),
+            WakuError::HttpRequest(e) => write!(f, "HTTP request failed: {}", e),
+            WakuError::Io(e) => write!(f, "I/O error: {}", e),
+            WakuError::ContainerStart(message)
+            | WakuError::NodeNotReady(message)
+            | WakuError::ParseResponse(message)
+            | WakuError::Timeout(message)
+            | WakuError::Failed(message)
+            | WakuError::Config(message) => f.write_str(message),
+            WakuError::Context { message, source } => write!(f, "{}: {}", message, source),
+        }
+    }
+}
+
+impl std::error::Error for WakuError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WakuError::DockerConnect(e) | WakuError::Docker(e) => Some(e),
+            WakuError::HttpRequest(e) => Some(e),
+            WakuError::Io(e) => Some(e),
+            WakuError::Context { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<bollard::errors::Error> for WakuError {
+    fn from(e: bollard::errors::Error) -> Self {
+        WakuError::Docker(e)
+    }
+}
+
+impl From<reqwest::Error> for WakuError {
+    fn from(e: reqwest::Error) -> Self {
+        WakuError::HttpRequest(e)
+    }
+}
+
+impl From<std::io::Error> for WakuError {
+    fn from(e: std::io::Error) -> Self {
+        WakuError::Io(e)
+    }
+}
+
+macro_rules! parse_errors {
+    ($($error:ty),*) => {
+        $(impl From<$error> for WakuError {
+            fn from(e: $error) -> Self {
+                WakuError::ParseResponse(e.to_string())
+            }
+        })*
+    };
+}
+
+parse_errors!(
+    serde_json::Error,
+    std::num::ParseIntError,
+    std::num::TryFromIntError,
+    std::net::AddrParseError,
+    std::string::FromUtf8Error,
+    base64::DecodeError,
+    reqwest::header::ToStrError
+);
+
+macro_rules! failed_errors {
+    ($($error:ty),*) => {
+        $(impl From<$error> for WakuError {
+            fn from(e: $error) -> Self {
+                WakuError::Failed(e.to_string())
+            }
+        })*
+    };
+}
+
+failed_errors!(
+    std::time::SystemTimeError,
+    tokio::task::JoinError,
+    tokio::sync::AcquireError
+);
+
+// Mirrors anyhow's context API so call sites read the same
+trait Context<T> {
+    fn context<C: std::fmt::Display>(self, context: C) -> Result<T>;
+    fn with_context<C: std::fmt::Display, F: FnOnce() -> C>(self, f: F) -> Result<T>;
+}
+
+impl<T, E: Into<WakuError>> Context<T> for std::result::Result<T, E> {
+    fn context<C: std::fmt::Display>(self, context: C) -> Result<T> {
+        self.map_err(|e| {
+            let e: WakuError = e.into();
+            e.context(context)
+        })
+    }
+
+    fn with_context<C: std::fmt::Display, F: FnOnce() -> C>(self, f: F) -> Result<T> {
+        self.map_err(|e| {
+            let e: WakuError = e.into();
+            e.context(f())
+        })
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context<C: std::fmt::Display>(self, context: C) -> Result<T> {
+        self.ok_or_else(|| WakuError::Failed(context.to_string()))
+    }
+
+    fn with_context<C: std::fmt::Display, F: FnOnce() -> C>(self, f: F) -> Result<T> {
+        self.ok_or_else(|| WakuError::Failed(f().to_string()))
+    }
+}
+
+const PROPAGATION_TIMEOUT: Duration = Duration::from_secs(60);
+const PEER_EXCHANGE_TIMEOUT: Duration = Duration::from_secs(60);
+const REMOVAL_TIMEOUT: Duration = Duration::from_secs(30);
+const REST_TIMEOUT: Duration = Duration::from_secs(30);
+const STARTUP_PEER_TIMEOUT: Duration = Duration::from_secs(60);
+const STORE_PROTOCOL_PREFIX: &str = "/vac/waku/store";
+const DISCV5_ROUTING_TABLE_METRIC: &str = "routing_table_nodes";
+const GOSSIPSUB_PEER_SCORE_METRIC: &str = "libp2p_gossipsub_peers_scores";
+const STORE_DB_MOUNT: &str = "/waku-store";
+const STORE_DB_FILE: &str = "store.sqlite3";
+const WSS_CERT_MOUNT: &str = "/waku-certs";
+const WSS_CERT_FILE: &str = "cert.pem";
+const WSS_KEY_FILE: &str = "key.pem";
+const RLN_KEYSTORE_MOUNT: &str = "/waku-rln";
+const NODE_LABEL: &str = "waku-test-automation.node";
+const DEFAULT_IMAGE: &str = "wakuorg/nwaku:v0.24.0";
+// nwaku's default --max-msg-size
+const DEFAULT_MAX_MSG_SIZE_KIB: u32 = 150;
+// Above the low host numbers tests tend to pick by hand with subnet_ip
+const AUTO_HOST_START: u32 = 100;
+
 #[derive(Debug, Clone)]
 pub struct WakuNode {
     pub container_id: String,
@@ -18,6 +192,11 @@ pub struct WakuNode {
     pub discv5_port: u16,
     pub external_ip: String,
     pub enr_uri: Option<String>,
+    pub store_db_dir: Option<PathBuf>,
+    pub metrics_port: Option<u16>,
+    pub hostname: Option<String>,
+    pub rln: Option<RlnConfig>,
+    pub max_msg_size_kib: u32,
 }
 
 #[derive(Debug, Deserialize)]
@@ -38,15 +217,233 @@ pub struct Message {
     pub payload: String,
     #[serde(rename = "contentTopic")]
     pub content_topic: String,
+    // Nanoseconds since the Unix epoch, as nwaku and the store expect
     pub timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ephemeral: Option<bool>,
 }
 
-#[derive(Debug, Deserialize)]
+impl Message {
+    pub fn ephemeral(mut self) -> Self {
+        self.ephemeral = Some(true);
+        self
+    }
+
+    pub fn with_timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelayVerification {
+    pub a_to_b: bool,
+    pub b_to_a: bool,
+}
+
+impl RelayVerification {
+    pub fn bidirectional(&self) -> bool {
+        self.a_to_b && self.b_to_a
+    }
+
+    pub fn any(&self) -> bool {
+        self.a_to_b || self.b_to_a
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TimestampCheck {
+    pub timestamp: u64,
+    pub accepted: bool,
+    pub status: u16,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StoreQuery {
+    pub content_topics: Vec<String>,
+    pub pubsub_topic: Option<String>,
+    // Nanoseconds since the Unix epoch, as the store API expects
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+    pub hashes: Vec<String>,
+    pub cursor: Option<String>,
+}
+
+impl StoreQuery {
+    pub fn last(window: Duration) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        Self {
+            start_time: Some(now.saturating_sub(window).as_nanos() as i64),
+            ..Default::default()
+        }
+    }
+
+    pub fn content_topic(mut self, topic: &str) -> Self {
+        self.content_topics.push(topic.to_string());
+        self
+    }
+
+    pub fn hash(mut self, hash: &str) -> Self {
+        self.hashes.push(hash.to_string());
+        self
+    }
+
+    fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = Vec::new();
+
+        if !self.content_topics.is_empty() {
+            params.push(("contentTopics", self.content_topics.join(",")));
+        }
+        if let Some(pubsub_topic) = &self.pubsub_topic {
+            params.push(("pubsubTopic", pubsub_topic.clone()));
+        }
+        if let Some(start_time) = self.start_time {
+            params.push(("startTime", start_time.to_string()));
+        }
+        if let Some(end_time) = self.end_time {
+            params.push(("endTime", end_time.to_string()));
+        }
+        if !self.hashes.is_empty() {
+            params.push(("hashes", self.hashes.join(",")));
+        }
+        if let Some(cursor) = &self.cursor {
+            params.push(("cursor", cursor.clone()));
+        }
+
+        params
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PeerConnectResult {
+    pub connected: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+impl PeerConnectResult {
+    pub fn all_connected(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthReport {
+    pub healthy: bool,
+    pub status: String,
+    pub protocols: HashMap<String, String>,
+}
+
+impl HealthReport {
+    pub fn protocol_ready(&self, protocol: &str) -> bool {
+        self.protocols
+            .iter()
+            .any(|(name, state)| name.eq_ignore_ascii_case(protocol) && state.eq_ignore_ascii_case("ready"))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FilterSubscription {
+    #[serde(rename = "requestId")]
+    pub request_id: String,
+    #[serde(rename = "contentFilters")]
+    pub content_filters: Vec<String>,
+    #[serde(rename = "pubsubTopic")]
+    pub pubsub_topic: String,
+}
+
+impl FilterSubscription {
+    pub fn new(pubsub_topic: &str, content_topics: &[&str]) -> Self {
+        Self {
+            request_id: uuid::Uuid::new_v4().to_string(),
+            content_filters: content_topics.iter().map(|topic| topic.to_string()).collect(),
+            pubsub_topic: pubsub_topic.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "serde_json::Value")]
+pub struct StoreResponse {
+    pub messages: Vec<ReceivedMessage>,
+    pub cursor: Option<String>,
+}
+
+impl TryFrom<serde_json::Value> for StoreResponse {
+    type Error = WakuError;
+
+    // Store v3 wraps each message with its hash, so this can't be a plain field mapping
+    fn try_from(body: serde_json::Value) -> Result<Self> {
+        Ok(Self {
+            messages: parse_store_messages(&body)?,
+            cursor: parse_store_cursor(&body),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct ReceivedMessage {
     pub payload: String,
     #[serde(rename = "contentTopic")]
     pub content_topic: String,
     pub timestamp: u64,
+    #[serde(default, rename = "messageHash")]
+    pub hash: Option<String>,
+}
+
+impl ReceivedMessage {
+    pub fn decoded_payload(&self) -> Result<Vec<u8>> {
+        use base64::{Engine, engine::general_purpose};
+
+        general_purpose::STANDARD
+            .decode(&self.payload)
+            .context("Failed to decode message payload")
+    }
+
+    pub fn decompressed_payload(&self, compression: Compression) -> Result<Vec<u8>> {
+        compression.decompress(&self.decoded_payload()?)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MessageDiff {
+    pub only_in_a: Vec<ReceivedMessage>,
+    pub only_in_b: Vec<ReceivedMessage>,
+    pub common: Vec<ReceivedMessage>,
+}
+
+impl MessageDiff {
+    pub fn is_identical(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PublishResponse {
+    pub status: u16,
+    pub body: String,
+    pub message_hash: Option<String>,
+}
+
+impl PublishResponse {
+    fn from_body(status: u16, body: String) -> Self {
+        let message_hash = serde_json::from_str::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|value| {
+                ["messageHash", "hash"]
+                    .iter()
+                    .find_map(|key| value.get(key).and_then(|v| v.as_str()).map(str::to_string))
+            })
+            .or_else(|| {
+                let trimmed = body.trim();
+                trimmed.starts_with("0x").then(|| trimmed.to_string())
+            });
+
+        Self { status, body, message_hash }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -55,42 +452,318 @@ pub struct PeerInfo {
     pub peer_id: String,
     pub multiaddr: String,
     pub connected: bool,
+    #[serde(default)]
+    pub origin: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_protocols")]
+    pub protocols: Vec<String>,
+    // Only reported by nwaku versions that track the libp2p identify agent
+    #[serde(default)]
+    pub agent: Option<String>,
+}
+
+impl PeerInfo {
+    pub fn supports(&self, protocol_prefix: &str) -> bool {
+        self.protocols.iter().any(|p| p.starts_with(protocol_prefix))
+    }
+}
+
+// Older nwaku reports protocols as {protocol, connected} objects, newer releases as plain strings
+fn deserialize_protocols<'de, D>(deserializer: D) -> std::result::Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let values = Vec::<serde_json::Value>::deserialize(deserializer)?;
+
+    Ok(values
+        .into_iter()
+        .filter_map(|value| match value {
+            serde_json::Value::String(protocol) => Some(protocol),
+            other => other.get("protocol").and_then(|p| p.as_str()).map(str::to_string),
+        })
+        .collect())
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameworkOptions {
+    pub default_headers: HeaderMap,
+    pub retry: RetryPolicy,
+    pub network_name: String,
+    pub name_prefix: String,
+    pub subnet: String,
+    pub gateway: String,
+    pub dynamic_ports: bool,
+    pub rest_access: RestAccess,
+    pub message_poll: PollBackoff,
+    pub keep_on_failure: bool,
+    pub max_concurrent_docker_ops: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PollBackoff {
+    pub initial: Duration,
+    pub max: Duration,
+}
+
+impl PollBackoff {
+    pub fn next(&self, current: Duration) -> Duration {
+        current.saturating_mul(2).min(self.max)
+    }
+}
+
+impl Default for PollBackoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(50),
+            max: Duration::from_secs(2),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RestAccess {
+    #[default]
+    PublishedPorts,
+    // For hosts where ports can't be published: run curl inside the node container instead
+    DockerExec,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExecOutput {
+    pub exit_code: i64,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[derive(Debug, Clone)]
+struct RestResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: String,
+}
+
+impl RestResponse {
+    fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        serde_json::from_str(&self.body).context("Failed to parse REST response")
+    }
+}
+
+impl Default for FrameworkOptions {
+    fn default() -> Self {
+        Self {
+            default_headers: HeaderMap::new(),
+            retry: RetryPolicy::default(),
+            network_name: "waku".to_string(),
+            name_prefix: String::new(),
+            subnet: "172.18.0.0/16".to_string(),
+            gateway: "172.18.0.1".to_string(),
+            dynamic_ports: false,
+            rest_access: RestAccess::PublishedPorts,
+            message_poll: PollBackoff::default(),
+            keep_on_failure: false,
+            max_concurrent_docker_ops: 8,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub retry_publish_on_5xx: bool,
+}
+
+impl RetryPolicy {
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_delay.saturating_mul(factor).min(self.max_delay)
+    }
+
+    pub async fn run<T, F, Fut>(&self, mut operation: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let max_attempts = self.max_attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            match operation().await {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < max_attempts => {
+                    debug!("Attempt {} of {} failed: {}", attempt, max_attempts, e);
+                    sleep(self.delay_for(attempt)).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(5),
+            retry_publish_on_5xx: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ContainerStats {
+    pub cpu_percent: f64,
+    pub memory_bytes: u64,
+    pub memory_limit_bytes: u64,
 }
 
+#[derive(Debug, Clone)]
+pub struct ContainerConfigDump {
+    pub image: Option<String>,
+    pub cmd: Vec<String>,
+    pub env: Vec<String>,
+    pub host_config: Option<HostConfig>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SmokeStep {
+    pub name: String,
+    pub success: bool,
+    pub duration: Duration,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SmokeReport {
+    pub steps: Vec<SmokeStep>,
+}
+
+impl SmokeReport {
+    pub fn passed(&self) -> bool {
+        self.steps.iter().all(|step| step.success)
+    }
+
+    fn record<T>(&mut self, name: &str, started: Instant, result: &Result<T>) {
+        self.steps.push(SmokeStep {
+            name: name.to_string(),
+            success: result.is_ok(),
+            duration: started.elapsed(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        });
+    }
+}
+
+// Messages drained from nwaku's relay cache but not yet consumed, keyed by (container id, topic)
+type RelayBuffer = HashMap<(String, String), Vec<ReceivedMessage>>;
+
+#[derive(Clone)]
 pub struct WakuTestFramework {
     docker: Docker,
     client: Client,
     network_name: String,
+    options: FrameworkOptions,
+    subscriptions: Arc<Mutex<HashMap<String, BTreeSet<String>>>>,
+    docker_ops: Arc<Semaphore>,
+    next_auto_host: Arc<AtomicU32>,
+    relay_buffer: Arc<Mutex<RelayBuffer>>,
 }
 
 impl WakuTestFramework {
     pub fn new() -> Result<Self> {
+        Self::with_options(FrameworkOptions::default())
+    }
+
+    pub fn with_options(options: FrameworkOptions) -> Result<Self> {
         let docker = Docker::connect_with_local_defaults()
-            .context("Failed to connect to Docker daemon")?;
+            .map_err(WakuError::DockerConnect)?;
         
         let client = Client::builder()
-            .timeout(Duration::from_secs(30))
+            .timeout(REST_TIMEOUT)
+            .default_headers(options.default_headers.clone())
             .build()
             .context("Failed to create HTTP client")?;
 
         Ok(Self {
             docker,
             client,
-            network_name: "waku".to_string(),
+            network_name: options.network_name.clone(),
+            docker_ops: Arc::new(Semaphore::new(options.max_concurrent_docker_ops.max(1))),
+            next_auto_host: Arc::new(AtomicU32::new(AUTO_HOST_START)),
+            relay_buffer: Arc::new(Mutex::new(HashMap::new())),
+            options,
+            subscriptions: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    pub fn isolated() -> Result<Self> {
+        let id = uuid::Uuid::new_v4();
+        let tag = id.simple().to_string()[..8].to_string();
+        let bytes = id.as_bytes();
+
+        Self::with_options(FrameworkOptions {
+            network_name: format!("waku-{}", tag),
+            name_prefix: format!("{}-", tag),
+            subnet: format!("10.{}.{}.0/24", bytes[0], bytes[1]),
+            gateway: format!("10.{}.{}.1", bytes[0], bytes[1]),
+            dynamic_ports: true,
+            ..Default::default()
         })
     }
 
+    pub fn options(&self) -> &FrameworkOptions {
+        &self.options
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.options.retry = retry;
+        self
+    }
+
+    pub fn subnet_ip(&self, host: u32) -> Result<String> {
+        let (base, prefix_len) = self.options.subnet
+            .split_once('/')
+            .context("Subnet must be in CIDR notation")?;
+        let base: std::net::Ipv4Addr = base.parse().context("Invalid subnet address")?;
+        let prefix_len: u32 = prefix_len.parse().context("Invalid subnet prefix length")?;
+
+        let host_bits = 32u32.saturating_sub(prefix_len);
+        if host_bits < 32 && u64::from(host) >= 1u64 << host_bits {
+            return Err(WakuError::Failed(format!("Host {} does not fit in subnet {}", host, self.options.subnet)));
+        }
+
+        Ok(std::net::Ipv4Addr::from(u32::from(base) + host).to_string())
+    }
+
+    pub fn subnet_contains(&self, ip: &str) -> Result<bool> {
+        let (base, prefix_len) = self.options.subnet
+            .split_once('/')
+            .context("Subnet must be in CIDR notation")?;
+        let base: std::net::Ipv4Addr = base.parse().context("Invalid subnet address")?;
+        let prefix_len: u32 = prefix_len.parse().context("Invalid subnet prefix length")?;
+        let Ok(ip) = ip.parse::<std::net::Ipv4Addr>() else {
+            return Ok(false);
+        };
+
+        let mask = u32::MAX.checked_shl(32u32.saturating_sub(prefix_len)).unwrap_or(0);
+        Ok(u32::from(ip) & mask == u32::from(base) & mask)
+    }
+
     pub async fn setup_network(&self) -> Result<()> {
-        info!("Creating Docker network: {}", self.network_name);
+        self.create_network(&self.network_name, &self.options.subnet, &self.options.gateway).await
+    }
+
+    pub async fn create_network(&self, name: &str, subnet: &str, gateway: &str) -> Result<()> {
+        info!("Creating Docker network: {}", name);
         
         let config = CreateNetworkOptions {
-            name: self.network_name.clone(),
+            name: name.to_string(),
             driver: "bridge".to_string(),
             ipam: Ipam {
                 driver: Some("default".to_string()),
                 config: Some(vec![IpamConfig {
-                    subnet: Some("172.18.0.0/16".to_string()),
-                    gateway: Some("172.18.0.1".to_string()),
+                    subnet: Some(subnet.to_string()),
+                    gateway: Some(gateway.to_string()),
                     ..Default::default()
                 }]),
                 ..Default::default()
@@ -109,320 +782,2995 @@ impl WakuTestFramework {
         Ok(())
     }
 
-    pub async fn start_waku_node(&self, node_config: WakuNodeConfig) -> Result<WakuNode> {
-        info!("Starting Waku node: {}", node_config.name);
+    pub async fn recreate_network(&self) -> Result<()> {
+        info!("Recreating Docker network: {}", self.network_name);
 
-        let port_bindings = create_port_bindings(&node_config);
-        let cmd = create_waku_command(&node_config);
+        let network = self.docker
+            .inspect_network(&self.network_name, None::<InspectNetworkOptions<String>>)
+            .await
+            .context("Failed to inspect network")?;
 
-        let config = Config {
-            image: Some("wakuorg/nwaku:v0.24.0".to_string()),
-            cmd: Some(cmd),
-            exposed_ports: Some(create_exposed_ports(&node_config)),
-            host_config: Some(HostConfig {
-                port_bindings: Some(port_bindings),
-                ..Default::default()
-            }),
-            ..Default::default()
-        };
+        let attached: Vec<(String, Option<String>)> = network
+            .containers
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(id, container)| {
+                let ip = container
+                    .ipv4_address
+                    .and_then(|addr| addr.split('/').next().map(str::to_string))
+                    .filter(|ip| !ip.is_empty());
+                (id, ip)
+            })
+            .collect();
 
-        let container = self.docker
-            .create_container(Some(CreateContainerOptions {
-                name: node_config.name.clone(),
-                platform: None,
-            }), config)
-            .await
-            .context("Failed to create container")?;
+        for (container_id, _) in &attached {
+            self.docker
+                .disconnect_network(&self.network_name, DisconnectNetworkOptions {
+                    container: container_id.clone(),
+                    force: true,
+                })
+                .await
+                .context("Failed to disconnect container from network")?;
+        }
 
         self.docker
-            .start_container(&container.id, None::<StartContainerOptions<String>>)
+            .remove_network(&self.network_name)
             .await
-            .context("Failed to start container")?;
+            .context("Failed to remove network")?;
+        self.setup_network().await?;
 
-        // Wait for container to be ready with longer timeout
-        sleep(Duration::from_secs(8)).await;
+        for (container_id, ip) in attached {
+            debug!("Reconnecting container {} at {:?}", container_id, ip);
+            let config = ConnectNetworkOptions {
+                container: container_id,
+                endpoint_config: EndpointSettings {
+                    ip_address: ip,
+                    ..Default::default()
+                },
+            };
 
-        let node = WakuNode {
-            container_id: container.id,
-            name: node_config.name,
-            rest_port: node_config.rest_port,
-            tcp_port: node_config.tcp_port,
-            websocket_port: node_config.websocket_port,
-            discv5_port: node_config.discv5_port,
-            external_ip: node_config.external_ip,
-            enr_uri: None,
-        };
-        
-        Ok(node)
+            self.docker
+                .connect_network(&self.network_name, config)
+                .await
+                .context("Failed to reconnect container to network")?;
+        }
+
+        Ok(())
     }
 
-    pub async fn connect_to_network(&self, node: &WakuNode) -> Result<()> {
-        info!("Connecting node {} to network {}", node.name, self.network_name);
+    pub async fn start_waku_node(&self, mut node_config: WakuNodeConfig) -> Result<WakuNode> {
+        if node_config.image.trim().is_empty() {
+            return Err(WakuError::Failed(format!("Node {} has an empty Docker image", node_config.name)));
+        }
+
+        let inspected = {
+            let _permit = self.docker_permit().await?;
+            self.docker.inspect_image(&node_config.image).await
+        };
+        match inspected {
+            Ok(_) => {}
+            Err(bollard::errors::Error::DockerResponseServerError { status_code: 404, .. }) if node_config.auto_pull => {
+                self.pull_image(&node_config.image).await?;
+            }
+            Err(bollard::errors::Error::DockerResponseServerError { status_code: 404, .. }) => {
+                return Err(WakuError::Failed(format!(
+                    "Image {} is not available locally and auto_pull is disabled", node_config.image
+                )));
+            }
+            Err(e) => return Err(e).context("Failed to inspect image"),
+        }
+
+        node_config.name = format!("{}{}", self.options.name_prefix, node_config.name);
+        if self.options.dynamic_ports {
+            node_config.assign_dynamic_ports()?;
+        }
+        // Default configs carry an address from the default subnet, which an isolated network rejects
+        if !self.subnet_contains(&node_config.external_ip)? {
+            let host = self.next_auto_host.fetch_add(1, Ordering::Relaxed);
+            let ip = self.subnet_ip(host)?;
+            debug!("Node {} address {} is outside {}, using {}", node_config.name, node_config.external_ip, self.options.subnet, ip);
+            node_config.external_ip = ip;
+        }
+
+        info!("Starting Waku node: {}", node_config.name);
 
-        let config = ConnectNetworkOptions {
-            container: node.container_id.clone(),
-            endpoint_config: EndpointSettings {
-                ip_address: Some(node.external_ip.clone()),
+        // Exec mode is for hosts that can't publish ports, and talks to the node from inside anyway
+        let publish_ports = self.options.rest_access != RestAccess::DockerExec;
+        let cmd = create_waku_command(&node_config);
+
+        let config = Config {
+            image: Some(node_config.image.clone()),
+            hostname: node_config.hostname.clone(),
+            labels: Some(HashMap::from([(NODE_LABEL.to_string(), "true".to_string())])),
+            cmd: Some(cmd),
+            exposed_ports: publish_ports.then(|| create_exposed_ports(&node_config)),
+            host_config: Some(HostConfig {
+                port_bindings: publish_ports.then(|| create_port_bindings(&node_config)),
+                binds: create_binds(&node_config),
+                dns: (!node_config.dns_servers.is_empty()).then(|| node_config.dns_servers.clone()),
+                log_config: create_log_config(&node_config),
+                ulimits: create_ulimits(&node_config),
                 ..Default::default()
-            },
+            }),
+            ..Default::default()
         };
 
-        self.docker
-            .connect_network(&self.network_name, config)
+        let permit = self.docker_permit().await?;
+
+        let container = self.docker
+            .create_container(Some(CreateContainerOptions {
+                name: node_config.name.clone(),
+                platform: None,
+            }), config)
             .await
-            .context("Failed to connect container to network")?;
+            .map_err(|e| WakuError::ContainerStart(format!("Failed to create container {}: {}", node_config.name, e)))?;
 
-        // Wait a bit for network connection to be established
-        sleep(Duration::from_secs(2)).await;
+        let readiness_timeout = node_config.readiness_timeout;
+        let require_peer = node_config.require_peer_on_start
+            && (node_config.bootstrap_node.is_some()
+                || !node_config.bootstrap_nodes.is_empty()
+                || !node_config.static_nodes.is_empty());
 
-        Ok(())
-    }
+        let node = WakuNode {
+            container_id: container.id,
+            name: node_config.name,
+            rest_port: node_config.rest_port,
+            tcp_port: node_config.tcp_port,
+            websocket_port: node_config.websocket_port,
+            discv5_port: node_config.discv5_port,
+            external_ip: node_config.external_ip,
+            enr_uri: None,
+            store_db_dir: node_config.store_db_dir,
+            metrics_port: node_config.metrics_port,
+            hostname: node_config.hostname,
+            rln: node_config.rln,
+            max_msg_size_kib: node_config.max_msg_size_kib.unwrap_or(DEFAULT_MAX_MSG_SIZE_KIB),
+        };
 
-    pub async fn get_node_info(&self, node: &WakuNode) -> Result<NodeInfo> {
-        let url = format!("http://127.0.0.1:{}/debug/v1/info", node.rest_port);
-        
-        for attempt in 1..=10 { // Increased attempts
-            debug!("Attempting to get node info (attempt {})", attempt);
-            match self.client.get(&url).send().await {
-                Ok(response) if response.status().is_success() => {
-                    // Try parsing as direct NodeInfo first, then as wrapped response
-                    let text = response.text().await
-                        .context("Failed to get response text")?;
-                    
-                    // Try direct parsing first
-                    if let Ok(node_info) = serde_json::from_str::<NodeInfo>(&text) {
-                        info!("Successfully got node info for {}", node.name);
-                        return Ok(node_info);
-                    }
-                    
-                    // Try wrapped response format
-                    if let Ok(api_response) = serde_json::from_str::<ApiResponse<NodeInfo>>(&text) {
-                        info!("Successfully got node info for {}", node.name);
-                        return Ok(api_response.data);
-                    }
-                    
-                    // Log the actual response for debugging
-                    warn!("Unable to parse node info response: {}", text);
-                    return Err(anyhow::anyhow!("Failed to parse node info response"));
+        // From here on the container exists, so any failure must remove it again
+        let started = async {
+            self.docker
+                .start_container(&node.container_id, None::<StartContainerOptions<String>>)
+                .await
+                .map_err(|e| WakuError::ContainerStart(format!("Failed to start container {}: {}", node.name, e)))?;
+            drop(permit);
+
+            self.wait_for_ready(&node, readiness_timeout).await?;
+
+            if require_peer {
+                // Peers are only reachable over the framework network, so join it before waiting
+                self.connect_to_network(&node).await?;
+
+                if !self.wait_for_peer_connection(&node, STARTUP_PEER_TIMEOUT.as_secs()).await? {
+                    return Err(WakuError::NodeNotReady(format!(
+                        "Node {} had no connected peer within {:?} of starting",
+                        node.name, STARTUP_PEER_TIMEOUT
+                    )));
                 }
-                Ok(response) => {
-                    warn!("Node info request failed with status: {}", response.status());
+            }
+            Ok::<_, WakuError>(())
+        }
+        .await;
+
+        if let Err(e) = started {
+            if let Err(cleanup) = self.cleanup_node(&node).await {
+                warn!("Failed to clean up node {} after startup failure: {}", node.name, cleanup);
+            }
+            return Err(e);
+        }
+
+        Ok(node)
+    }
+
+    pub async fn start_scoped_node(&self, config: WakuNodeConfig) -> Result<ScopedWakuNode> {
+        let node = self.start_waku_node(config).await?;
+        Ok(ScopedWakuNode { node: Some(node) })
+    }
+
+    pub async fn start_cluster(&self, configs: Vec<WakuNodeConfig>) -> Result<Vec<WakuNode>> {
+        let mut configs = configs.into_iter();
+        let Some(first_config) = configs.next() else {
+            return Ok(Vec::new());
+        };
+
+        // The first node has to be up and networked before its ENR is usable as a bootstrap
+        let mut first = self.start_waku_node(first_config).await?;
+        let bootstrap = async {
+            self.connect_to_network(&first).await?;
+            self.refresh_enr(&mut first).await?;
+            first.enr_uri.clone().context("Bootstrap node has no ENR")
+        }
+        .await;
+        let bootstrap_enr = match bootstrap {
+            Ok(enr) => enr,
+            Err(e) => {
+                self.cleanup_after_failed_start(&[first]).await;
+                return Err(e);
+            }
+        };
+
+        let starts = configs.map(|mut config| {
+            if config.bootstrap_node.is_none() && config.bootstrap_nodes.is_empty() {
+                config.bootstrap_node = Some(bootstrap_enr.clone());
+            }
+            async move {
+                // start_waku_node removes its own container when it fails
+                let node = self.start_waku_node(config).await.map_err(|e| (e, None))?;
+                match self.connect_to_network(&node).await {
+                    Ok(()) => Ok(node),
+                    Err(e) => Err((e, Some(node))),
                 }
-                Err(e) => {
-                    warn!("Node info request failed (attempt {}): {}", attempt, e);
+            }
+        });
+        let results = futures_util::future::join_all(starts).await;
+
+        let mut nodes = vec![first];
+        let mut first_error = None;
+        for result in results {
+            match result {
+                Ok(node) => nodes.push(node),
+                Err((e, node)) => {
+                    nodes.extend(node);
+                    first_error.get_or_insert(e);
                 }
             }
-            
-            if attempt < 10 {
-                sleep(Duration::from_secs(3)).await; // Longer sleep between attempts
+        }
+
+        if let Some(e) = first_error {
+            self.cleanup_after_failed_start(&nodes).await;
+            return Err(e.context("Failed to start cluster"));
+        }
+
+        info!("Started cluster of {} nodes", nodes.len());
+        Ok(nodes)
+    }
+
+    async fn cleanup_after_failed_start(&self, nodes: &[WakuNode]) {
+        for node in nodes {
+            if let Err(e) = self.cleanup_node(node).await {
+                warn!("Failed to clean up node {} after cluster start failure: {}", node.name, e);
             }
         }
-        
-        Err(anyhow::anyhow!("Failed to get node info after 10 attempts"))
     }
 
-    pub async fn subscribe_to_topic(&self, node: &WakuNode, topic: &str) -> Result<()> {
-        let url = format!("http://127.0.0.1:{}/relay/v1/auto/subscriptions", node.rest_port);
-        let payload = json!([topic]);
+    async fn wait_for_ready(&self, node: &WakuNode, timeout: Duration) -> Result<()> {
+        let start = Instant::now();
+        let mut delay = Duration::from_millis(100);
 
-        let response = self.client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .json(&payload)
-            .send()
-            .await
-            .context("Failed to send subscription request")?;
+        let failure = loop {
+            match self.rest_request(node, Method::GET, "/debug/v1/info", None).await {
+                Ok(response) if response.status.is_success() => {
+                    info!("Node {} ready after {:?}", node.name, start.elapsed());
+                    return Ok(());
+                }
+                Ok(response) => debug!("Node {} not ready yet: {}", node.name, response.status),
+                Err(e) => debug!("Node {} not ready yet: {}", node.name, e),
+            }
 
-        if response.status().is_success() {
-            info!("Successfully subscribed node {} to topic {}", node.name, topic);
-            Ok(())
-        } else {
-            Err(anyhow::anyhow!("Subscription failed with status: {}", response.status()))
-        }
+            // No point waiting out the timeout if the process has already died
+            let running = {
+                let _permit = self.docker_permit().await?;
+                self.docker.inspect_container(&node.container_id, None).await
+            }
+            .ok()
+            .and_then(|inspect| inspect.state?.running);
+            if running == Some(false) {
+                break format!("container {} exited before becoming ready", node.name);
+            }
+
+            if start.elapsed() >= timeout {
+                break format!("node {} was not ready within {:?}", node.name, timeout);
+            }
+            sleep(delay).await;
+            delay = (delay * 2).min(Duration::from_secs(2));
+        };
+
+        let logs = self.get_container_logs(node, Some(50), None).await
+            .unwrap_or_else(|e| format!("<failed to read logs: {}>", e));
+        Err(WakuError::NodeNotReady(format!("Startup failed: {}; last log lines:\n{}", failure, logs.trim_end())))
     }
 
-    pub async fn publish_message(&self, node: &WakuNode, message: &Message) -> Result<()> {
-        let url = format!("http://127.0.0.1:{}/relay/v1/auto/messages", node.rest_port);
+    // Bounded so that starting many nodes at once doesn't overwhelm the daemon
+    async fn docker_permit(&self) -> Result<SemaphorePermit<'_>> {
+        self.docker_ops.acquire().await.context("Docker operation limiter closed")
+    }
 
-        let response = self.client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .json(message)
-            .send()
-            .await
-            .context("Failed to send publish request")?;
+    pub async fn pull_image(&self, image: &str) -> Result<()> {
+        info!("Pulling image {}", image);
 
-        if response.status().is_success() {
-            info!("Successfully published message from node {}", node.name);
-            Ok(())
-        } else {
-            Err(anyhow::anyhow!("Message publication failed with status: {}", response.status()))
+        let options = CreateImageOptions {
+            from_image: image,
+            ..Default::default()
+        };
+
+        let _permit = self.docker_permit().await?;
+        let mut stream = Box::pin(self.docker.create_image(Some(options), None, None));
+        while let Some(progress) = stream.next().await {
+            let progress = progress.with_context(|| format!("Failed to pull image {}", image))?;
+            debug!(
+                "Pull {}: {} {}",
+                image,
+                progress.status.unwrap_or_default(),
+                progress.progress.unwrap_or_default()
+            );
         }
+
+        info!("Pulled image {}", image);
+        Ok(())
     }
 
-    pub async fn get_messages(&self, node: &WakuNode, topic: &str) -> Result<Vec<ReceivedMessage>> {
-        let encoded_topic = urlencoding::encode(topic);
-        let url = format!("http://127.0.0.1:{}/relay/v1/auto/messages/{}", 
-                         node.rest_port, encoded_topic);
+    pub async fn connect_to_network(&self, node: &WakuNode) -> Result<()> {
+        self.connect_to_named_network(node, &self.network_name, Some(&node.external_ip)).await
+    }
 
-        let response = self.client
-            .get(&url)
-            .send()
-            .await
-            .context("Failed to get messages")?;
+    // ip is None for secondary networks, where Docker assigns an address from that network's subnet
+    pub async fn connect_to_named_network(&self, node: &WakuNode, network: &str, ip: Option<&str>) -> Result<()> {
+        info!("Connecting node {} to network {}", node.name, network);
 
-        if response.status().is_success() {
-            let messages: Vec<ReceivedMessage> = response.json().await
-                .context("Failed to parse messages response")?;
-            debug!("Got {} messages from node {}", messages.len(), node.name);
-            Ok(messages)
-        } else {
-            debug!("No messages found for node {} on topic {}", node.name, topic);
-            Ok(vec![])
+        let retry = &self.options.retry;
+        let max_attempts = retry.max_attempts.max(1);
+        for attempt in 1..=max_attempts {
+            let config = ConnectNetworkOptions {
+                container: node.container_id.clone(),
+                endpoint_config: EndpointSettings {
+                    ip_address: ip.map(str::to_string),
+                    // Docker's embedded DNS resolves aliases, not the container hostname itself
+                    aliases: node.hostname.clone().map(|hostname| vec![hostname]),
+                    ..Default::default()
+                },
+            };
+
+            match self.docker.connect_network(network, config).await {
+                Ok(_) => break,
+                Err(bollard::errors::Error::DockerResponseServerError { status_code: 403, message })
+                    if message.contains("already exists") =>
+                {
+                    debug!("Node {} is already connected to network {}", node.name, network);
+                    return Ok(());
+                }
+                Err(e) if attempt < max_attempts && is_transient_docker_error(&e) => {
+                    warn!("Connecting node {} to network failed (attempt {}): {}", node.name, attempt, e);
+                    sleep(retry.delay_for(attempt)).await;
+                }
+                Err(e) => return Err(e).context("Failed to connect container to network"),
+            }
         }
+
+        // Wait a bit for network connection to be established
+        sleep(Duration::from_secs(2)).await;
+
+        Ok(())
     }
 
-    pub async fn get_peers(&self, node: &WakuNode) -> Result<Vec<PeerInfo>> {
-        let url = format!("http://127.0.0.1:{}/admin/v1/peers", node.rest_port);
+    pub async fn disconnect_from_network(&self, node: &WakuNode) -> Result<()> {
+        self.disconnect_from_named_network(node, &self.network_name).await
+    }
 
-        let response = self.client
-            .get(&url)
-            .send()
+    pub async fn disconnect_from_named_network(&self, node: &WakuNode, network: &str) -> Result<()> {
+        info!("Disconnecting node {} from network {}", node.name, network);
+
+        self.docker
+            .disconnect_network(network, DisconnectNetworkOptions {
+                container: node.container_id.clone(),
+                force: true,
+            })
             .await
-            .context("Failed to get peers")?;
+            .context("Failed to disconnect container from network")?;
 
-        if response.status().is_success() {
-            let peers: Vec<PeerInfo> = response.json().await
-                .context("Failed to parse peers response")?;
-            debug!("Node {} has {} peers", node.name, peers.len());
-            Ok(peers)
-        } else {
-            debug!("Failed to get peers for node {}", node.name);
-            Ok(vec![])
-        }
+        Ok(())
     }
 
-    pub async fn connect_peer(&self, node: &WakuNode, peer_multiaddr: &str) -> Result<()> {
-        let url = format!("http://127.0.0.1:{}/admin/v1/peers", node.rest_port);
-        let payload = json!({"multiaddr": peer_multiaddr});
+    pub async fn assert_subscription_persists(
+        &self,
+        node: &WakuNode,
+        publisher: &WakuNode,
+        topic: &str,
+        network_timeout: Duration,
+    ) -> Result<()> {
+        self.disconnect_from_network(node).await?;
+        sleep(network_timeout).await;
+        self.connect_to_network(node).await?;
 
-        let response = self.client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .json(&payload)
-            .send()
-            .await
-            .context("Failed to send connect peer request")?;
+        // A probe published before the link is back would be lost regardless of the subscription
+        for peer in [node, publisher] {
+            if self.wait_for_peer_count(peer, 1, PROPAGATION_TIMEOUT).await? == 0 {
+                return Err(WakuError::Failed(format!(
+                    "Node {} had no connected peers within {:?} of reconnecting {}",
+                    peer.name, PROPAGATION_TIMEOUT, node.name
+                )));
+            }
+        }
 
-        if response.status().is_success() {
-            info!("Successfully connected node {} to peer {}", node.name, peer_multiaddr);
-            Ok(())
-        } else {
-            warn!("Failed to connect to peer: {}", response.status());
-            Ok(()) // Don't fail, as discovery might still work
+        // Deliberately no re-subscribe: the probe only comes back if the subscription survived.
+        // It's published from a peer so it has to cross the reconnected link.
+        let probe = create_test_message(&format!("subscription-probe-{}", uuid::Uuid::new_v4()), topic);
+        self.publish_message(publisher, &probe).await?;
+
+        let received = self
+            .wait_for_message(node, topic, PROPAGATION_TIMEOUT, |m| m.payload == probe.payload)
+            .await?;
+
+        if received.is_none() {
+            return Err(WakuError::Failed(format!(
+                "Node {} stopped receiving messages on {} after reconnecting",
+                node.name, topic
+            )));
         }
+
+        info!("Subscription of node {} to {} survived reconnect", node.name, topic);
+        Ok(())
     }
 
-    pub async fn wait_for_peer_connection(&self, node: &WakuNode, timeout_secs: u64) -> Result<bool> {
-        let start = std::time::Instant::now();
-        
-        while start.elapsed().as_secs() < timeout_secs {
-            if let Ok(peers) = self.get_peers(node).await {
-                debug!("Node {} has {} peers", node.name, peers.len());
-                if !peers.is_empty() && peers.iter().any(|p| p.connected) {
-                    info!("Node {} is connected to peers", node.name);
-                    return Ok(true);
-                }
+    pub async fn get_node_info(&self, node: &WakuNode) -> Result<NodeInfo> {
+        let text = self.options.retry.run(move || async move {
+            let response = self.rest_request(node, Method::GET, "/debug/v1/info", None).await?;
+            if !response.status.is_success() {
+                return Err(WakuError::Failed(format!("Node info request failed with status: {}", response.status)));
             }
-            sleep(Duration::from_secs(5)).await;
+            Ok::<_, WakuError>(response.body)
+        })
+        .await
+        .map_err(|e| WakuError::NodeNotReady(format!("Failed to get node info for {}: {}", node.name, e)))?;
+
+        // Try parsing as direct NodeInfo first, then as wrapped response
+        if let Ok(node_info) = serde_json::from_str::<NodeInfo>(&text) {
+            info!("Successfully got node info for {}", node.name);
+            return Ok(node_info);
         }
-        
-        warn!("Node {} did not connect to any peers within {} seconds", node.name, timeout_secs);
-        Ok(false)
+
+        if let Ok(api_response) = serde_json::from_str::<ApiResponse<NodeInfo>>(&text) {
+            info!("Successfully got node info for {}", node.name);
+            return Ok(api_response.data);
+        }
+
+        // Log the actual response for debugging
+        warn!("Unable to parse node info response: {}", text);
+        Err(WakuError::ParseResponse("Failed to parse node info response".to_string()))
     }
 
-    pub async fn cleanup_existing_containers(&self) -> Result<()> {
-        use bollard::container::ListContainersOptions;
-        
-        let options = Some(ListContainersOptions::<String> {
-            all: true,
-            filters: {
-                let mut filters = std::collections::HashMap::new();
-                filters.insert("name".to_string(), vec!["waku-node".to_string()]);
-                filters
-            },
-            ..Default::default()
-        });
+    pub async fn wait_until_healthy(&self, node: &WakuNode, timeout: Duration) -> Result<HealthReport> {
+        let start = Instant::now();
+        let mut delay = self.options.message_poll.initial;
 
-        let containers = self.docker.list_containers(options).await?;
-        
-        for container in containers {
-            if let Some(id) = container.id {
-                info!("Cleaning up existing container: {}", id);
-                let _ = self.docker.stop_container(&id, None).await;
-                let _ = self.docker.remove_container(&id, None).await;
+        loop {
+            let last = match self.rest_request(node, Method::GET, "/health", None).await {
+                Ok(response) => {
+                    let report = parse_health(response.status, &response.body);
+                    if report.healthy {
+                        info!("Node {} is healthy after {:?}", node.name, start.elapsed());
+                        return Ok(report);
+                    }
+                    format!("{} {:?}", report.status, report.protocols)
+                }
+                Err(e) => e.to_string(),
+            };
+
+            if start.elapsed() >= timeout {
+                return Err(WakuError::NodeNotReady(format!(
+                    "Node {} was not healthy within {:?}; last state: {}",
+                    node.name, timeout, last
+                )));
             }
+            sleep(delay).await;
+            delay = self.options.message_poll.next(delay);
         }
-        
-        Ok(())
     }
 
-    pub async fn cleanup_node(&self, node: &WakuNode) -> Result<()> {
-        info!("Cleaning up node: {}", node.name);
-        
-        // Stop and remove container
-        if let Err(e) = self.docker.stop_container(&node.container_id, None).await {
-            warn!("Failed to stop container {}: {}", node.container_id, e);
+    pub async fn get_version(&self, node: &WakuNode) -> Result<String> {
+        let response = self
+            .rest_request(node, Method::GET, "/debug/v1/version", None)
+            .await
+            .context("Failed to get node version")?;
+
+        if !response.status.is_success() {
+            return Err(WakuError::Failed(format!("Version request failed with status: {}", response.status)));
         }
-        
-        if let Err(e) = self.docker.remove_container(&node.container_id, None).await {
-            warn!("Failed to remove container {}: {}", node.container_id, e);
+
+        // Some releases return a JSON string, others plain text
+        let version = serde_json::from_str::<String>(&response.body)
+            .unwrap_or_else(|_| response.body.trim().to_string());
+        debug!("Node {} runs version {}", node.name, version);
+        Ok(version)
+    }
+
+    pub async fn require_version_at_least(&self, node: &WakuNode, minimum: &str) -> Result<()> {
+        let version = self.get_version(node).await?;
+        let actual = parse_version(&version)?;
+        let required = parse_version(minimum)?;
+
+        if actual < required {
+            return Err(WakuError::Failed(format!(
+                "Node {} runs nwaku {} but at least {} is required",
+                node.name, version, minimum
+            )));
         }
-        
+
         Ok(())
     }
 
-    pub async fn cleanup_network(&self) -> Result<()> {
-        info!("Cleaning up network: {}", self.network_name);
-        
-        if let Err(e) = self.docker.remove_network(&self.network_name).await {
-            warn!("Failed to remove network {}: {}", self.network_name, e);
+    /// Re-reads the node's ENR into `node.enr_uri`. nwaku bumps the ENR sequence
+    /// number whenever its advertised record changes, e.g. after the container joins
+    /// a network and the external address becomes reachable, or when discv5 updates
+    /// the observed IP/port, so call this after network changes before handing the
+    /// ENR to other nodes.
+    pub async fn refresh_enr(&self, node: &mut WakuNode) -> Result<()> {
+        let node_info = self.get_node_info(node).await?;
+
+        if node.enr_uri.as_deref() != Some(node_info.enr_uri.as_str()) {
+            debug!("ENR for node {} updated to {}", node.name, node_info.enr_uri);
         }
-        
+        node.enr_uri = Some(node_info.enr_uri);
+
         Ok(())
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct WakuNodeConfig {
-    pub name: String,
-    pub rest_port: u16,
-    pub tcp_port: u16,
-    pub websocket_port: u16,
-    pub discv5_port: u16,
-    pub external_ip: String,
-    pub bootstrap_node: Option<String>,
-}
+    pub async fn collect_enrs(&self, nodes: &[WakuNode]) -> Vec<String> {
+        let mut enrs = Vec::with_capacity(nodes.len());
 
-impl Default for WakuNodeConfig {
-    fn default() -> Self {
-        Self {
-            name: "waku-node".to_string(),
-            rest_port: 22161, // Changed from 21161 to avoid conflicts
-            tcp_port: 22162,
-            websocket_port: 22163,
-            discv5_port: 22164,
-            external_ip: "172.18.111.226".to_string(),
-            bootstrap_node: None,
+        for node in nodes {
+            match &node.enr_uri {
+                Some(enr) => enrs.push(enr.clone()),
+                None => match self.get_node_info(node).await {
+                    Ok(info) => enrs.push(info.enr_uri),
+                    Err(e) => warn!("Skipping ENR of node {}: {}", node.name, e),
+                },
+            }
         }
+
+        enrs
     }
-}
 
-fn create_port_bindings(config: &WakuNodeConfig) -> HashMap<String, Option<Vec<PortBinding>>> {
-    let mut bindings = HashMap::new();
-    
-    bindings.insert(
-        format!("{}/tcp", config.rest_port),
+    pub async fn node_time(&self, node: &WakuNode) -> Result<SystemTime> {
+        // nwaku has no clock endpoint, but its REST server stamps every response with a Date header
+        let response = self
+            .rest_request(node, Method::GET, "/debug/v1/info", None)
+            .await
+            .context("Failed to query node time")?;
+
+        let date = response
+            .headers
+            .get(reqwest::header::DATE)
+            .with_context(|| format!("Node {} did not send a Date header", node.name))?
+            .to_str()
+            .context("Date header is not valid ASCII")?;
+
+        parse_http_date(date)
+    }
+
+    pub async fn clock_skew_secs(&self, node: &WakuNode) -> Result<i64> {
+        let before = SystemTime::now();
+        let node_time = self.node_time(node).await?;
+        let after = SystemTime::now();
+
+        let midpoint = before + after.duration_since(before).unwrap_or_default() / 2;
+        let skew = match node_time.duration_since(midpoint) {
+            Ok(ahead) => ahead.as_secs() as i64,
+            Err(behind) => -(behind.duration().as_secs() as i64),
+        };
+
+        debug!("Node {} clock skew is {}s", node.name, skew);
+        Ok(skew)
+    }
+
+    pub async fn verify_enr_external_ip(&self, node: &WakuNode) -> Result<bool> {
+        let node_info = self.get_node_info(node).await?;
+        let enr_ip = decode_enr_ip(&node_info.enr_uri)?;
+
+        match enr_ip {
+            Some(ip) if ip.to_string() == node.external_ip => Ok(true),
+            Some(ip) => {
+                warn!("Node {} advertises {} in its ENR, expected {}", node.name, ip, node.external_ip);
+                Ok(false)
+            }
+            None => {
+                warn!("Node {} ENR does not contain an IPv4 address", node.name);
+                Ok(false)
+            }
+        }
+    }
+
+    pub async fn wss_multiaddr(&self, node: &WakuNode) -> Result<String> {
+        let node_info = self.get_node_info(node).await?;
+
+        node_info
+            .listen_addresses
+            .into_iter()
+            .find(|addr| addr.contains("/wss"))
+            .with_context(|| format!("Node {} does not advertise a wss listen address", node.name))
+    }
+
+    // A wss relay subscription needs a libp2p client (noise, yamux, gossipsub), which this crate
+    // doesn't have, so subscribe_wss is out of scope. This goes as far as a browser client's first
+    // step: a websocket upgrade over TLS that the node must answer with 101 Switching Protocols.
+    pub async fn check_wss_endpoint(&self, node: &WakuNode) -> Result<()> {
+        use base64::{Engine, engine::general_purpose};
+
+        if self.options.rest_access == RestAccess::DockerExec {
+            return Err(WakuError::Failed("check_wss_endpoint needs the websocket port published".to_string()));
+        }
+
+        // Test certificates are self-signed; the handshake itself is what's under test
+        let client = Client::builder()
+            .danger_accept_invalid_certs(true)
+            .http1_only()
+            .default_headers(self.options.default_headers.clone())
+            .timeout(Duration::from_secs(10))
+            .build()
+            .context("Failed to create TLS client")?;
+
+        let url = format!("https://127.0.0.1:{}/", node.websocket_port);
+        let key = general_purpose::STANDARD.encode(uuid::Uuid::new_v4().as_bytes());
+        let response = client
+            .get(&url)
+            .header(reqwest::header::CONNECTION, "Upgrade")
+            .header(reqwest::header::UPGRADE, "websocket")
+            .header(reqwest::header::SEC_WEBSOCKET_KEY, key)
+            .header(reqwest::header::SEC_WEBSOCKET_VERSION, "13")
+            .send()
+            .await
+            .with_context(|| format!("TLS handshake with node {} websocket port failed", node.name))?;
+
+        let upgraded = response
+            .headers()
+            .get(reqwest::header::UPGRADE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+        if response.status() != StatusCode::SWITCHING_PROTOCOLS || !upgraded {
+            return Err(WakuError::Failed(format!(
+                "Node {} did not accept a websocket upgrade over TLS: {}",
+                node.name, response.status()
+            )));
+        }
+
+        debug!("wss endpoint of node {} accepted the websocket upgrade", node.name);
+        Ok(())
+    }
+
+    pub async fn subscribe_to_topic(&self, node: &WakuNode, topic: &str) -> Result<()> {
+        let payload = &json!([topic]);
+
+        // Subscribing is idempotent, so every failure is safe to retry
+        self.options.retry.run(move || async move {
+            let response = self
+                .rest_request(node, Method::POST, "/relay/v1/auto/subscriptions", Some(payload.clone()))
+                .await
+                .context("Failed to send subscription request")?;
+
+            if !response.status.is_success() {
+                return Err(WakuError::Failed(format!("Subscription failed with status: {}", response.status)));
+            }
+            Ok::<_, WakuError>(())
+        })
+        .await?;
+
+        info!("Successfully subscribed node {} to topic {}", node.name, topic);
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .entry(node.container_id.clone())
+            .or_default()
+            .insert(topic.to_string());
+        Ok(())
+    }
+
+    pub async fn subscribe_all_shards(&self, node: &WakuNode, cluster_id: u16, num_shards: u16) -> Result<()> {
+        let topics: Vec<String> = (0..num_shards)
+            .map(|shard| pubsub_topic(cluster_id, shard))
+            .collect();
+
+        let response = self
+            .rest_request(node, Method::POST, "/relay/v1/subscriptions", Some(json!(topics)))
+            .await
+            .context("Failed to send shard subscription request")?;
+
+        if !response.status.is_success() {
+            return Err(WakuError::Failed(format!("Shard subscription failed with status: {}", response.status)));
+        }
+
+        info!("Subscribed node {} to {} shards of cluster {}", node.name, num_shards, cluster_id);
+        Ok(())
+    }
+
+    pub async fn subscribe_to_pubsub_topic(&self, node: &WakuNode, pubsub_topic: &str) -> Result<()> {
+        let response = self
+            .rest_request(node, Method::POST, "/relay/v1/subscriptions", Some(json!([pubsub_topic])))
+            .await
+            .context("Failed to send pubsub subscription request")?;
+
+        if !response.status.is_success() {
+            return Err(WakuError::Failed(format!("Pubsub subscription failed with status: {}", response.status)));
+        }
+
+        info!("Successfully subscribed node {} to pubsub topic {}", node.name, pubsub_topic);
+        Ok(())
+    }
+
+    pub async fn publish_to_pubsub_topic(&self, node: &WakuNode, pubsub_topic: &str, message: &Message) -> Result<PublishResponse> {
+        let payload = serde_json::to_value(message).context("Failed to serialize message")?;
+        let path = format!("/relay/v1/messages/{}", urlencoding::encode(pubsub_topic));
+
+        let response = self
+            .rest_request(node, Method::POST, &path, Some(payload))
+            .await
+            .context("Failed to send publish request")?;
+
+        if !response.status.is_success() {
+            return Err(WakuError::Failed(format!(
+                "Publishing to {} failed with status {}: {}",
+                pubsub_topic, response.status, response.body.trim()
+            )));
+        }
+
+        info!("Successfully published message from node {} to {}", node.name, pubsub_topic);
+        Ok(PublishResponse::from_body(response.status.as_u16(), response.body))
+    }
+
+    pub async fn get_pubsub_messages(&self, node: &WakuNode, pubsub_topic: &str) -> Result<Vec<ReceivedMessage>> {
+        let path = format!("/relay/v1/messages/{}", urlencoding::encode(pubsub_topic));
+
+        let response = self
+            .rest_request(node, Method::GET, &path, None)
+            .await
+            .context("Failed to get pubsub messages")?;
+
+        if !response.status.is_success() {
+            debug!("No messages found for node {} on pubsub topic {}", node.name, pubsub_topic);
+            return Ok(vec![]);
+        }
+
+        let messages: Vec<ReceivedMessage> = response.json()
+            .context("Failed to parse messages response")?;
+        debug!("Got {} messages from node {} on {}", messages.len(), node.name, pubsub_topic);
+        Ok(messages)
+    }
+
+    pub async fn publish_message(&self, node: &WakuNode, message: &Message) -> Result<PublishResponse> {
+        let payload = serde_json::to_value(message).context("Failed to serialize message")?;
+        // A retried publish can relay the message twice, so retries are opt-in
+        let mut retry = self.options.retry.clone();
+        if !retry.retry_publish_on_5xx {
+            retry.max_attempts = 1;
+        }
+
+        let payload = &payload;
+        let response = retry.run(move || async move {
+            // Only gateway errors are retried; a transport error may come after the node already relayed
+            let response = match self.rest_request(node, Method::POST, "/relay/v1/auto/messages", Some(payload.clone())).await {
+                Ok(response) => response,
+                Err(e) => return Ok(Err(e.context("Failed to send publish request"))),
+            };
+
+            if matches!(response.status.as_u16(), 502..=504) {
+                warn!("Publish on node {} returned {}", node.name, response.status);
+                return Err(WakuError::Failed(format!("Message publication failed with status: {}", response.status)));
+            }
+            Ok::<Result<RestResponse>, WakuError>(Ok(response))
+        })
+        .await??;
+
+        let status = response.status;
+        if status.is_success() {
+            info!("Successfully published message from node {}", node.name);
+            Ok(PublishResponse::from_body(status.as_u16(), response.body))
+        } else {
+            Err(WakuError::Failed(format!("Message publication failed with status: {}", status)))
+        }
+    }
+
+    pub async fn publish_with_timestamp_skew(
+        &self,
+        node: &WakuNode,
+        topic: &str,
+        skew_secs: i64,
+    ) -> Result<TimestampCheck> {
+        let message = create_test_message(&format!("timestamp-skew-{}", skew_secs), topic);
+        let timestamp = message.timestamp.saturating_add_signed(skew_secs.saturating_mul(1_000_000_000));
+        let message = message.with_timestamp(timestamp);
+
+        let payload = serde_json::to_value(&message).context("Failed to serialize message")?;
+        let response = self
+            .rest_request(node, Method::POST, "/relay/v1/auto/messages", Some(payload))
+            .await
+            .context("Failed to send publish request")?;
+
+        let status = response.status;
+        let body = response.body;
+        info!("Publish with timestamp skew {}s on node {} returned {}", skew_secs, node.name, status);
+
+        Ok(TimestampCheck {
+            timestamp,
+            accepted: status.is_success(),
+            status: status.as_u16(),
+            body,
+        })
+    }
+
+    pub async fn lightpush_message(&self, node: &WakuNode, pubsub_topic: &str, message: &Message) -> Result<()> {
+        let payload = json!({
+            "pubsubTopic": pubsub_topic,
+            "message": message,
+        });
+
+        let response = self
+            .rest_request(node, Method::POST, "/lightpush/v1/message", Some(payload))
+            .await
+            .context("Failed to send lightpush request")?;
+
+        if !response.status.is_success() {
+            return Err(WakuError::Failed(format!(
+                "Lightpush via node {} failed with status {}: {}",
+                node.name, response.status, response.body.trim()
+            )));
+        }
+
+        info!("Lightpushed message via node {} to {}", node.name, pubsub_topic);
+        Ok(())
+    }
+
+    pub async fn filter_subscribe(&self, node: &WakuNode, pubsub_topic: &str, content_topics: &[&str]) -> Result<String> {
+        let subscription = FilterSubscription::new(pubsub_topic, content_topics);
+        self.send_filter_request(node, Method::POST, &subscription).await?;
+
+        info!("Node {} filter-subscribed to {:?} (request {})", node.name, content_topics, subscription.request_id);
+        Ok(subscription.request_id)
+    }
+
+    pub async fn filter_unsubscribe(&self, node: &WakuNode, pubsub_topic: &str, content_topics: &[&str]) -> Result<String> {
+        let subscription = FilterSubscription::new(pubsub_topic, content_topics);
+        self.send_filter_request(node, Method::DELETE, &subscription).await?;
+
+        info!("Node {} filter-unsubscribed from {:?} (request {})", node.name, content_topics, subscription.request_id);
+        Ok(subscription.request_id)
+    }
+
+    async fn send_filter_request(&self, node: &WakuNode, method: Method, subscription: &FilterSubscription) -> Result<()> {
+        let payload = serde_json::to_value(subscription).context("Failed to encode filter subscription")?;
+
+        let response = self
+            .rest_request(node, method, "/filter/v2/subscriptions", Some(payload))
+            .await
+            .context("Failed to send filter subscription request")?;
+
+        if !response.status.is_success() {
+            return Err(WakuError::Failed(format!(
+                "Filter request {} on node {} failed with status {}: {}",
+                subscription.request_id, node.name, response.status, response.body.trim()
+            )));
+        }
+
+        Ok(())
+    }
+
+    // topic is a pubsub topic and selects /relay/v1/messages/{topic}; None uses the autosharding
+    // endpoint, where the body has to carry its own contentTopic
+    pub async fn publish_raw(
+        &self,
+        node: &WakuNode,
+        topic: Option<&str>,
+        body: String,
+        content_type: &str,
+    ) -> Result<reqwest::Response> {
+        // Callers get the raw reqwest response, which only exists when talking to a published port
+        if self.options.rest_access == RestAccess::DockerExec {
+            return Err(WakuError::Failed("publish_raw is not supported with RestAccess::DockerExec".to_string()));
+        }
+
+        let path = match topic {
+            Some(topic) => format!("/relay/v1/messages/{}", urlencoding::encode(topic)),
+            None => "/relay/v1/auto/messages".to_string(),
+        };
+        let url = format!("http://127.0.0.1:{}{}", node.rest_port, path);
+
+        let response = self.client
+            .post(&url)
+            .header("Content-Type", content_type)
+            .body(body)
+            .send()
+            .await
+            .context("Failed to send raw publish request")?;
+
+        debug!("Raw publish to node {} returned {}", node.name, response.status());
+        Ok(response)
+    }
+
+    // Publishes a well-formed message one byte over the node's --max-msg-size. The relay validator
+    // rejects it at the publishing node (the REST call answers 400), so it must never reach a subscriber.
+    // raw_payload prefixes the padding so assert_not_propagated can recognise the message.
+    pub async fn publish_invalid(&self, node: &WakuNode, topic: &str, raw_payload: &str) -> Result<reqwest::Response> {
+        let limit = node.max_msg_size_kib as usize * 1024;
+        let mut payload = raw_payload.as_bytes().to_vec();
+        payload.resize(payload.len().max(limit + 1), b'x');
+
+        let message = create_binary_message(&payload, topic);
+        let body = serde_json::to_string(&message).context("Failed to serialize message")?;
+        let response = self.publish_raw(node, None, body, "application/json").await?;
+
+        debug!("Oversized publish to node {} returned {}", node.name, response.status());
+        Ok(response)
+    }
+
+    pub async fn assert_not_propagated(
+        &self,
+        subscriber: &WakuNode,
+        topic: &str,
+        raw_payload: &str,
+        wait: Duration,
+    ) -> Result<()> {
+        let received = self
+            .wait_for_message(subscriber, topic, wait, |m| {
+                m.decoded_payload().is_ok_and(|bytes| bytes.starts_with(raw_payload.as_bytes()))
+            })
+            .await?;
+
+        if received.is_some() {
+            return Err(WakuError::Failed(format!(
+                "Invalid message was relayed to node {} on {}",
+                subscriber.name, topic
+            )));
+        }
+
+        info!("Invalid message was not relayed to node {}", subscriber.name);
+        Ok(())
+    }
+
+    // Consumes the returned messages; use peek_messages to look without consuming
+    pub async fn get_messages(&self, node: &WakuNode, topic: &str) -> Result<Vec<ReceivedMessage>> {
+        let fresh = self.drain_relay_cache(node, topic).await?;
+
+        let mut messages = self.relay_buffer
+            .lock()
+            .unwrap()
+            .remove(&(node.container_id.clone(), topic.to_string()))
+            .unwrap_or_default();
+        messages.extend(fresh);
+        Ok(messages)
+    }
+
+    pub async fn peek_messages(&self, node: &WakuNode, topic: &str) -> Result<Vec<ReceivedMessage>> {
+        let fresh = self.drain_relay_cache(node, topic).await?;
+
+        let mut buffer = self.relay_buffer.lock().unwrap();
+        let buffered = buffer.entry((node.container_id.clone(), topic.to_string())).or_default();
+        buffered.extend(fresh);
+        Ok(buffered.clone())
+    }
+
+    fn unconsume_messages(&self, node: &WakuNode, topic: &str, messages: Vec<ReceivedMessage>) {
+        if messages.is_empty() {
+            return;
+        }
+
+        // Ahead of anything buffered meanwhile, so arrival order is kept
+        let mut buffer = self.relay_buffer.lock().unwrap();
+        let buffered = buffer.entry((node.container_id.clone(), topic.to_string())).or_default();
+        buffered.splice(0..0, messages);
+    }
+
+    // nwaku's REST relay cache hands out each message once, so every call empties it
+    async fn drain_relay_cache(&self, node: &WakuNode, topic: &str) -> Result<Vec<ReceivedMessage>> {
+        let encoded_topic = urlencoding::encode(topic);
+        let path = format!("/relay/v1/auto/messages/{}", encoded_topic);
+
+        let response = self
+            .rest_request(node, Method::GET, &path, None)
+            .await
+            .context("Failed to get messages")?;
+
+        if response.status.is_success() {
+            let messages: Vec<ReceivedMessage> = response.json()
+                .context("Failed to parse messages response")?;
+            debug!("Got {} messages from node {}", messages.len(), node.name);
+            Ok(messages)
+        } else {
+            debug!("No messages found for node {} on topic {}", node.name, topic);
+            Ok(vec![])
+        }
+    }
+
+    pub async fn flush_relay_cache(&self, node: &WakuNode, topic: &str) -> Result<()> {
+        // Consuming read, so both nwaku's cache and the framework buffer end up empty
+        let drained = self.get_messages(node, topic).await?;
+
+        info!("Flushed {} cached messages on node {} for {}", drained.len(), node.name, topic);
+        Ok(())
+    }
+
+    pub async fn wait_for_message<F>(
+        &self,
+        node: &WakuNode,
+        topic: &str,
+        timeout: Duration,
+        predicate: F,
+    ) -> Result<Option<ReceivedMessage>>
+    where
+        F: Fn(&ReceivedMessage) -> bool,
+    {
+        let start = Instant::now();
+        let mut interval = self.options.message_poll.initial;
+
+        loop {
+            let mut messages = self.get_messages(node, topic).await?;
+            let matched = messages.iter().position(&predicate).map(|i| messages.remove(i));
+            // Leave everything else for later reads instead of discarding it
+            self.unconsume_messages(node, topic, messages);
+            if matched.is_some() {
+                return Ok(matched);
+            }
+
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                debug!("No matching message on node {} for topic {} within {:?}", node.name, topic, timeout);
+                return Ok(None);
+            }
+            sleep(interval.min(timeout - elapsed)).await;
+            interval = self.options.message_poll.next(interval);
+        }
+    }
+
+    pub async fn assert_roundtrip(
+        &self,
+        source: &WakuNode,
+        dest: &WakuNode,
+        topic: &str,
+        payload: &[u8],
+        timeout: Duration,
+    ) -> Result<()> {
+        let message = create_binary_message(payload, topic);
+        self.publish_message(source, &message).await?;
+
+        // Compare decoded bytes rather than the base64 text so re-encoding differences surface too
+        let received = self
+            .wait_for_message(dest, topic, timeout, |m| {
+                m.decoded_payload().is_ok_and(|bytes| bytes == payload)
+            })
+            .await?;
+
+        if received.is_none() {
+            return Err(WakuError::Timeout(format!(
+                "Node {} did not receive the exact {}-byte payload from {} within {:?}",
+                dest.name, payload.len(), source.name, timeout
+            )));
+        }
+
+        info!("{}-byte payload arrived intact from {} to {}", payload.len(), source.name, dest.name);
+        Ok(())
+    }
+
+    pub async fn verify_bidirectional_relay(
+        &self,
+        node_a: &WakuNode,
+        node_b: &WakuNode,
+        topic: &str,
+        timeout: Duration,
+    ) -> Result<RelayVerification> {
+        let mut delivered = [false; 2];
+
+        for (i, (source, dest)) in [(node_a, node_b), (node_b, node_a)].into_iter().enumerate() {
+            let message = create_test_message(&format!("relay-check-{}", uuid::Uuid::new_v4()), topic);
+            self.publish_message(source, &message).await?;
+
+            delivered[i] = self
+                .wait_for_message(dest, topic, timeout, |m| m.payload == message.payload)
+                .await?
+                .is_some();
+            debug!("Relay {} -> {}: delivered={}", source.name, dest.name, delivered[i]);
+        }
+
+        let verification = RelayVerification { a_to_b: delivered[0], b_to_a: delivered[1] };
+        info!("Relay between {} and {}: {:?}", node_a.name, node_b.name, verification);
+        Ok(verification)
+    }
+
+    pub async fn measure_propagation(&self, source: &WakuNode, dest: &WakuNode, topic: &str) -> Result<Duration> {
+        let probe = create_test_message(&format!("latency-probe-{}", uuid::Uuid::new_v4()), topic);
+
+        let start = Instant::now();
+        self.publish_message(source, &probe).await?;
+
+        let received = self
+            .wait_for_message(dest, topic, PROPAGATION_TIMEOUT, |m| m.payload == probe.payload)
+            .await?;
+
+        match received {
+            Some(_) => {
+                let elapsed = start.elapsed();
+                info!("Message propagated from {} to {} in {:?}", source.name, dest.name, elapsed);
+                Ok(elapsed)
+            }
+            None => Err(WakuError::Timeout(format!(
+                "Message from {} did not reach {} within {:?}",
+                source.name, dest.name, PROPAGATION_TIMEOUT
+            ))),
+        }
+    }
+
+    pub fn subscribed_topics(&self, node: &WakuNode) -> Vec<String> {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .get(&node.container_id)
+            .map(|topics| topics.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub async fn get_messages_matching(&self, node: &WakuNode, prefix: &str) -> Result<Vec<ReceivedMessage>> {
+        let mut matching = Vec::new();
+
+        for topic in self.subscribed_topics(node).iter().filter(|t| t.starts_with(prefix)) {
+            let messages = self.get_messages(node, topic).await?;
+            matching.extend(messages.into_iter().filter(|m| m.content_topic.starts_with(prefix)));
+        }
+
+        debug!("Got {} messages matching {} from node {}", matching.len(), prefix, node.name);
+        Ok(matching)
+    }
+
+    pub async fn total_cached_messages(&self, node: &WakuNode) -> Result<usize> {
+        let mut total = 0;
+
+        for topic in self.subscribed_topics(node) {
+            total += self.peek_messages(node, &topic).await?.len();
+        }
+
+        debug!("Node {} has {} cached messages across subscribed topics", node.name, total);
+        Ok(total)
+    }
+
+    pub async fn publish_and_assert_delivery_ratio(
+        &self,
+        source: &WakuNode,
+        subscribers: &[WakuNode],
+        topic: &str,
+        message: &Message,
+        min_ratio: f64,
+        timeout: Duration,
+    ) -> Result<f64> {
+        if subscribers.is_empty() {
+            return Err(WakuError::Failed("Delivery ratio requires at least one subscriber".to_string()));
+        }
+
+        self.publish_message(source, message).await?;
+
+        let waits = subscribers.iter().map(|subscriber| {
+            self.wait_for_message(subscriber, topic, timeout, |m| m.payload == message.payload)
+        });
+        let results = futures_util::future::join_all(waits).await;
+
+        let mut delivered = 0;
+        for (subscriber, result) in subscribers.iter().zip(results) {
+            match result {
+                Ok(Some(_)) => delivered += 1,
+                Ok(None) => debug!("Message was not delivered to node {}", subscriber.name),
+                Err(e) => warn!("Failed to check delivery on node {}: {}", subscriber.name, e),
+            }
+        }
+
+        let ratio = delivered as f64 / subscribers.len() as f64;
+        info!("Delivered to {}/{} subscribers ({:.1}%)", delivered, subscribers.len(), ratio * 100.0);
+
+        if ratio < min_ratio {
+            return Err(WakuError::Failed(format!(
+                "Delivery ratio {:.3} is below required {:.3}",
+                ratio, min_ratio
+            )));
+        }
+
+        Ok(ratio)
+    }
+
+    pub async fn assert_relay_order_preserved(
+        &self,
+        source: &WakuNode,
+        dest: &WakuNode,
+        topic: &str,
+        count: u64,
+    ) -> Result<()> {
+        let mut publisher = SequencedPublisher::new(self, source, topic);
+
+        // Each read drains the relay cache, so keep everything in arrival order. Reading between
+        // publishes keeps a count above the cache capacity from evicting the earliest messages.
+        let mut received = Vec::new();
+        let mut others = Vec::new();
+        let mut published = 0;
+        let mut waiting_since = None;
+        let mut delay = self.options.message_poll.initial;
+        loop {
+            if published < count {
+                publisher.publish_next().await?;
+                published += 1;
+            }
+
+            for message in self.get_messages(dest, topic).await? {
+                match sequence_number(&message) {
+                    Some(seq) => received.push(seq),
+                    None => others.push(message),
+                }
+            }
+
+            if (received.len() as u64) >= count {
+                break;
+            }
+            if published < count {
+                continue;
+            }
+            if waiting_since.get_or_insert_with(Instant::now).elapsed() >= PROPAGATION_TIMEOUT {
+                break;
+            }
+            sleep(delay).await;
+            delay = self.options.message_poll.next(delay);
+        }
+
+        // Unrelated traffic on the topic stays available to later reads
+        self.unconsume_messages(dest, topic, others);
+
+        if (received.len() as u64) < count {
+            return Err(WakuError::Timeout(format!(
+                "Node {} received {}/{} sequenced messages within {:?}",
+                dest.name, received.len(), count, PROPAGATION_TIMEOUT
+            )));
+        }
+
+        if let Some(pair) = received.windows(2).find(|pair| pair[0] >= pair[1]) {
+            return Err(WakuError::Failed(format!(
+                "Node {} received message {} after {}; relay order was not preserved: {:?}",
+                dest.name, pair[1], pair[0], received
+            )));
+        }
+
+        info!("Node {} received {} messages in publish order", dest.name, count);
+        Ok(())
+    }
+
+    pub async fn wait_for_store_peer(&self, node: &WakuNode, timeout: Duration) -> Result<bool> {
+        let start = Instant::now();
+
+        while start.elapsed() < timeout {
+            let peers = self.get_peers(node).await?;
+            if let Some(peer) = peers.iter().find(|p| p.connected && p.supports(STORE_PROTOCOL_PREFIX)) {
+                info!("Node {} is connected to store peer {}", node.name, peer.peer_id);
+                return Ok(true);
+            }
+            sleep(Duration::from_secs(1)).await;
+        }
+
+        warn!("Node {} has no connected store peer after {:?}", node.name, timeout);
+        Ok(false)
+    }
+
+    pub async fn wait_for_store_count(
+        &self,
+        store_node: &WakuNode,
+        content_topic: &str,
+        min_count: usize,
+        timeout: Duration,
+    ) -> Result<usize> {
+        let start = Instant::now();
+
+        loop {
+            let count = self
+                .fetch_store_messages(store_node, &StoreQuery::default().content_topic(content_topic))
+                .await?
+                .len();
+            debug!("Store node {} has {} messages on {}", store_node.name, count, content_topic);
+
+            if count >= min_count {
+                info!("Store node {} archived {} messages on {}", store_node.name, count, content_topic);
+                return Ok(count);
+            }
+
+            if start.elapsed() >= timeout {
+                return Err(WakuError::Timeout(format!(
+                    "Store node {} archived only {}/{} messages on {} within {:?}",
+                    store_node.name, count, min_count, content_topic, timeout
+                )));
+            }
+            sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    pub async fn publish_and_verify_stored(
+        &self,
+        relay_node: &WakuNode,
+        store_node: &WakuNode,
+        topic: &str,
+        message: &Message,
+        timeout: Duration,
+    ) -> Result<ReceivedMessage> {
+        let published = self.publish_message(relay_node, message).await?;
+
+        // Older nwaku versions don't return the hash on publish, so fall back to matching the payload
+        let query = match &published.message_hash {
+            Some(hash) => StoreQuery::default().hash(hash),
+            None => StoreQuery::default().content_topic(topic),
+        };
+
+        let start = Instant::now();
+        loop {
+            let stored = self.fetch_store_messages(store_node, &query).await?;
+            let found = stored.into_iter().find(|m| match &published.message_hash {
+                Some(hash) => m.hash.as_deref() == Some(hash.as_str()),
+                None => m.payload == message.payload,
+            });
+
+            if let Some(found) = found {
+                info!("Message from {} archived by store node {} after {:?}",
+                      relay_node.name, store_node.name, start.elapsed());
+                return Ok(found);
+            }
+
+            if start.elapsed() >= timeout {
+                return Err(WakuError::Timeout(format!(
+                    "Message published on {} was not archived by {} within {:?}",
+                    relay_node.name, store_node.name, timeout
+                )));
+            }
+            sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    pub async fn assert_not_in_store(&self, store_node: &WakuNode, message_hash: &str, wait: Duration) -> Result<()> {
+        sleep(wait).await;
+
+        let stored = self.fetch_store_messages(store_node, &StoreQuery::default().hash(message_hash)).await?;
+        if stored.iter().any(|m| m.hash.as_deref() == Some(message_hash)) {
+            return Err(WakuError::Failed(format!(
+                "Message {} was archived by store node {}",
+                message_hash, store_node.name
+            )));
+        }
+
+        info!("Message {} was not stored by node {}", message_hash, store_node.name);
+        Ok(())
+    }
+
+    pub async fn wait_for_store_expiry(&self, node: &WakuNode, message_hash: &str, timeout: Duration) -> Result<()> {
+        let start = Instant::now();
+        let query = StoreQuery::default().hash(message_hash);
+
+        loop {
+            let stored = self.fetch_store_messages(node, &query).await?;
+            if !stored.iter().any(|m| m.hash.as_deref() == Some(message_hash)) {
+                info!("Message {} expired from store node {} after {:?}", message_hash, node.name, start.elapsed());
+                return Ok(());
+            }
+
+            if start.elapsed() >= timeout {
+                return Err(WakuError::Timeout(format!(
+                    "Message {} was still stored by {} after {:?}",
+                    message_hash, node.name, timeout
+                )));
+            }
+            sleep(Duration::from_secs(2)).await;
+        }
+    }
+
+    pub async fn query_store_messages(&self, node: &WakuNode, query: &StoreQuery) -> Result<StoreResponse> {
+        self.query_store_messages_paginated(node, query, query.cursor.as_deref(), 100).await
+    }
+
+    pub async fn query_store_messages_paginated(
+        &self,
+        node: &WakuNode,
+        query: &StoreQuery,
+        cursor: Option<&str>,
+        page_size: usize,
+    ) -> Result<StoreResponse> {
+        let query = StoreQuery { cursor: cursor.map(str::to_string), ..query.clone() };
+        let mut params = query.params();
+        params.push(("includeData", "true".to_string()));
+        params.push(("pageSize", page_size.to_string()));
+
+        let path = format!("/store/v3/messages?{}", encode_query(&params));
+        let response = self
+            .rest_request(node, Method::GET, &path, None)
+            .await
+            .context("Failed to send store query")?;
+
+        if !response.status.is_success() {
+            return Err(WakuError::Failed(format!("Store query failed with status: {}", response.status)));
+        }
+
+        let page: StoreResponse = response.json()
+            .context("Failed to parse store response")?;
+        debug!("Store node {} returned {} messages", node.name, page.messages.len());
+        Ok(page)
+    }
+
+    pub fn store_message_pages<'a>(
+        &'a self,
+        node: &'a WakuNode,
+        query: StoreQuery,
+        page_size: usize,
+    ) -> impl futures_util::Stream<Item = Result<StoreResponse>> + 'a {
+        // State is the cursor for the next page; None once the store reports no further pages
+        futures_util::stream::try_unfold(Some(query.cursor.clone()), move |cursor| {
+            let query = query.clone();
+            async move {
+                let Some(cursor) = cursor else {
+                    return Ok(None);
+                };
+
+                let page = self
+                    .query_store_messages_paginated(node, &query, cursor.as_deref(), page_size)
+                    .await?;
+                let next = page.cursor.clone().map(Some);
+                Ok(Some((page, next)))
+            }
+        })
+    }
+
+    async fn fetch_store_messages(&self, node: &WakuNode, query: &StoreQuery) -> Result<Vec<ReceivedMessage>> {
+        let mut messages = Vec::new();
+        let mut query = query.clone();
+
+        loop {
+            let page = self.query_store_messages(node, &query).await?;
+            messages.extend(page.messages);
+
+            query.cursor = page.cursor;
+            if query.cursor.is_none() {
+                return Ok(messages);
+            }
+        }
+    }
+
+    pub async fn store_count(&self, node: &WakuNode, query: &StoreQuery) -> Result<usize> {
+        let mut count = 0;
+        let mut query = query.clone();
+
+        // Without includeData the store only returns message hashes, so pages stay small
+        loop {
+            let mut params = query.params();
+            params.push(("includeData", "false".to_string()));
+            params.push(("pageSize", "100".to_string()));
+
+            let path = format!("/store/v3/messages?{}", encode_query(&params));
+            let response = self
+                .rest_request(node, Method::GET, &path, None)
+                .await
+                .context("Failed to send store count query")?;
+
+            if !response.status.is_success() {
+                return Err(WakuError::Failed(format!("Store count query failed with status: {}", response.status)));
+            }
+
+            let body: serde_json::Value = response.json()
+                .context("Failed to parse store response")?;
+            count += body["messages"].as_array().map_or(0, Vec::len);
+
+            query.cursor = parse_store_cursor(&body);
+            if query.cursor.is_none() {
+                debug!("Store node {} holds {} matching messages", node.name, count);
+                return Ok(count);
+            }
+        }
+    }
+
+    pub async fn get_peers(&self, node: &WakuNode) -> Result<Vec<PeerInfo>> {
+        let response = self
+            .rest_request(node, Method::GET, "/admin/v1/peers", None)
+            .await
+            .context("Failed to get peers")?;
+
+        if response.status.is_success() {
+            let peers: Vec<PeerInfo> = response.json()
+                .context("Failed to parse peers response")?;
+            debug!("Node {} has {} peers", node.name, peers.len());
+            Ok(peers)
+        } else {
+            debug!("Failed to get peers for node {}", node.name);
+            Ok(vec![])
+        }
+    }
+
+    pub async fn dial_multiaddr(&self, node: &WakuNode) -> Result<String> {
+        let info = self.get_node_info(node).await?;
+        tcp_multiaddr(&info, node)
+            .with_context(|| format!("Node {} has no dialable TCP address", node.name))
+    }
+
+    pub async fn connect_peer(&self, node: &WakuNode, peer_multiaddr: &str) -> Result<()> {
+        let result = self.connect_peers(node, &[peer_multiaddr.to_string()]).await?;
+
+        if !result.all_connected() {
+            return Err(WakuError::Failed(format!("Node {} failed to connect to {}", node.name, peer_multiaddr)));
+        }
+        Ok(())
+    }
+
+    pub async fn connect_peers(&self, node: &WakuNode, multiaddrs: &[String]) -> Result<PeerConnectResult> {
+        let response = self
+            .rest_request(node, Method::POST, "/admin/v1/peers", Some(json!(multiaddrs)))
+            .await
+            .context("Failed to send connect peer request")?;
+
+        if !response.status.is_success() {
+            // nwaku rejects the whole request if any dial fails, so check each peer below
+            warn!("Connect peers on node {} returned {}: {}", node.name, response.status, response.body.trim());
+        }
+
+        let connected_ids: BTreeSet<String> = self.get_peers(node).await?
+            .into_iter()
+            .filter(|p| p.connected)
+            .map(|p| p.peer_id)
+            .collect();
+
+        let mut result = PeerConnectResult::default();
+        for multiaddr in multiaddrs {
+            let connected = match multiaddr.split("/p2p/").nth(1) {
+                Some(peer_id) => connected_ids.contains(peer_id.trim_end_matches('/')),
+                // Without a peer ID the only signal we have is the response status
+                None => response.status.is_success(),
+            };
+
+            if connected {
+                result.connected.push(multiaddr.clone());
+            } else {
+                result.failed.push(multiaddr.clone());
+            }
+        }
+
+        info!(
+            "Node {} connected to {}/{} requested peers",
+            node.name, result.connected.len(), multiaddrs.len()
+        );
+        if !result.failed.is_empty() {
+            warn!("Node {} failed to connect to: {:?}", node.name, result.failed);
+        }
+
+        Ok(result)
+    }
+
+    pub async fn connected_peer_count(&self, node: &WakuNode) -> Result<usize> {
+        let count = self.get_peers(node).await?.iter().filter(|p| p.connected).count();
+        debug!("Node {} has {} connected peers", node.name, count);
+        Ok(count)
+    }
+
+    pub async fn assert_max_connections_enforced(
+        &self,
+        node: &WakuNode,
+        peers: &[WakuNode],
+        max_connections: usize,
+    ) -> Result<usize> {
+        if peers.len() <= max_connections {
+            return Err(WakuError::Failed(format!(
+                "Need more than {} peers to exceed the connection limit, got {}",
+                max_connections, peers.len()
+            )));
+        }
+
+        let mut multiaddrs = Vec::with_capacity(peers.len());
+        for peer in peers {
+            multiaddrs.push(self.dial_multiaddr(peer).await?);
+        }
+        // Dials past the limit are expected to fail, so only the resulting count matters
+        self.connect_peers(node, &multiaddrs).await?;
+
+        // Give the connection manager time to prune any excess connections
+        sleep(Duration::from_secs(5)).await;
+
+        let connected = self.connected_peer_count(node).await?;
+        if connected > max_connections {
+            return Err(WakuError::Failed(format!(
+                "Node {} has {} connected peers, exceeding its limit of {}",
+                node.name, connected, max_connections
+            )));
+        }
+
+        info!("Node {} capped connections at {} after {} dials", node.name, connected, peers.len());
+        Ok(connected)
+    }
+
+    pub async fn request_peer_exchange(&self, node: &WakuNode, from_peer: &WakuNode) -> Result<Vec<PeerInfo>> {
+        let from_multiaddr = self.dial_multiaddr(from_peer).await?;
+        self.connect_peer(node, &from_multiaddr).await?;
+
+        let start = Instant::now();
+        loop {
+            let exchanged: Vec<PeerInfo> = self.get_peers(node).await?
+                .into_iter()
+                .filter(|p| p.origin.as_deref() == Some("PeerExchange"))
+                .collect();
+
+            if !exchanged.is_empty() {
+                info!("Node {} learned {} peers via peer exchange", node.name, exchanged.len());
+                return Ok(exchanged);
+            }
+
+            if start.elapsed() >= PEER_EXCHANGE_TIMEOUT {
+                warn!("Node {} learned no peers via peer exchange from {}", node.name, from_peer.name);
+                return Ok(exchanged);
+            }
+            sleep(Duration::from_secs(2)).await;
+        }
+    }
+
+    pub async fn wait_for_peer_count(&self, node: &WakuNode, min_peers: usize, timeout: Duration) -> Result<usize> {
+        let start = Instant::now();
+
+        loop {
+            let count = self.connected_peer_count(node).await?;
+            if count >= min_peers {
+                info!("Node {} has {} connected peers (wanted {})", node.name, count, min_peers);
+                return Ok(count);
+            }
+
+            if start.elapsed() >= timeout {
+                warn!("Node {} has only {}/{} connected peers after {:?}", node.name, count, min_peers, timeout);
+                return Ok(count);
+            }
+            sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    pub async fn wait_for_peer_connection(&self, node: &WakuNode, timeout_secs: u64) -> Result<bool> {
+        self.wait_for_peer_connection_cancellable(node, timeout_secs, &CancellationToken::new()).await
+    }
+
+    pub async fn wait_for_peer_connection_cancellable(
+        &self,
+        node: &WakuNode,
+        timeout_secs: u64,
+        cancel: &CancellationToken,
+    ) -> Result<bool> {
+        let start = std::time::Instant::now();
+        
+        while start.elapsed().as_secs() < timeout_secs {
+            if let Ok(peers) = self.get_peers(node).await {
+                debug!("Node {} has {} peers", node.name, peers.len());
+                if !peers.is_empty() && peers.iter().any(|p| p.connected) {
+                    info!("Node {} is connected to peers", node.name);
+                    return Ok(true);
+                }
+            }
+
+            tokio::select! {
+                _ = cancel.cancelled() => {
+                    return Err(WakuError::Failed(format!("Wait for peers on node {} was cancelled", node.name)));
+                }
+                _ = sleep(Duration::from_secs(5)) => {}
+            }
+        }
+        
+        warn!("Node {} did not connect to any peers within {} seconds", node.name, timeout_secs);
+        Ok(false)
+    }
+
+    pub async fn container_stats(&self, node: &WakuNode) -> Result<ContainerStats> {
+        // A non-streaming request makes Docker take two samples, so precpu_stats is populated
+        let options = StatsOptions {
+            stream: false,
+            one_shot: false,
+        };
+
+        let mut stream = Box::pin(self.docker.stats(&node.container_id, Some(options)));
+        let stats = stream
+            .next()
+            .await
+            .context("Docker returned no stats")?
+            .context("Failed to get container stats")?;
+
+        let cpu_delta = stats.cpu_stats.cpu_usage.total_usage as f64
+            - stats.precpu_stats.cpu_usage.total_usage as f64;
+        let system_delta = stats.cpu_stats.system_cpu_usage.unwrap_or(0) as f64
+            - stats.precpu_stats.system_cpu_usage.unwrap_or(0) as f64;
+        let online_cpus = stats.cpu_stats.online_cpus.unwrap_or(1) as f64;
+
+        let cpu_percent = if cpu_delta > 0.0 && system_delta > 0.0 {
+            cpu_delta / system_delta * online_cpus * 100.0
+        } else {
+            0.0
+        };
+
+        let container_stats = ContainerStats {
+            cpu_percent,
+            memory_bytes: stats.memory_stats.usage.unwrap_or(0),
+            memory_limit_bytes: stats.memory_stats.limit.unwrap_or(0),
+        };
+        debug!("Node {} stats: {:?}", node.name, container_stats);
+
+        Ok(container_stats)
+    }
+
+    pub fn store_db_path(&self, node: &WakuNode) -> Option<PathBuf> {
+        node.store_db_dir.as_ref().map(|dir| dir.join(STORE_DB_FILE))
+    }
+
+    pub async fn get_metrics(&self, node: &WakuNode) -> Result<HashMap<String, f64>> {
+        let metrics_port = node.metrics_port
+            .with_context(|| format!("Node {} was started without a metrics port", node.name))?;
+
+        let response = self
+            .http_request(node, metrics_port, Method::GET, "/metrics", None)
+            .await
+            .context("Failed to get metrics")?;
+
+        if !response.status.is_success() {
+            return Err(WakuError::Failed(format!("Metrics request failed with status: {}", response.status)));
+        }
+
+        Ok(parse_metrics(&response.body))
+    }
+
+    pub async fn discv5_table_size(&self, node: &WakuNode) -> Result<u64> {
+        let metrics = self.get_metrics(node).await?;
+
+        // nim-eth labels recently seen nodes separately from the routing table proper
+        let size: f64 = metrics
+            .iter()
+            .filter(|(series, _)| metric_name(series) == DISCV5_ROUTING_TABLE_METRIC)
+            .filter(|(series, _)| !series.contains("state=\"seen\""))
+            .map(|(_, value)| *value)
+            .sum();
+
+        debug!("Node {} discv5 routing table has {} nodes", node.name, size);
+        Ok(size as u64)
+    }
+
+    pub async fn sample_metrics(
+        &self,
+        node: &WakuNode,
+        metric_names: &[&str],
+        interval: Duration,
+        duration: Duration,
+    ) -> Result<Vec<(Instant, HashMap<String, f64>)>> {
+        let framework = self.clone();
+        let node = node.clone();
+        let names: Vec<String> = metric_names.iter().map(|name| name.to_string()).collect();
+
+        let sampler = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            let mut samples = Vec::new();
+            let start = Instant::now();
+
+            while start.elapsed() < duration {
+                let at = ticker.tick().await.into_std();
+                let sample: HashMap<String, f64> = framework.get_metrics(&node).await?
+                    .into_iter()
+                    .filter(|(series, _)| names.iter().any(|name| name == metric_name(series)))
+                    .collect();
+                samples.push((at, sample));
+            }
+
+            Ok::<_, WakuError>(samples)
+        });
+
+        let samples = sampler.await.context("Metrics sampling task panicked")??;
+        info!("Collected {} metric samples", samples.len());
+        Ok(samples)
+    }
+
+    pub async fn peer_scores(&self, node: &WakuNode) -> Result<HashMap<String, f64>> {
+        let metrics = self.get_metrics(node).await?;
+
+        // nim-libp2p aggregates scores per agent rather than per peer id, so that is the key we can offer
+        let scores: HashMap<String, f64> = metrics
+            .iter()
+            .filter(|(series, _)| metric_name(series) == GOSSIPSUB_PEER_SCORE_METRIC)
+            .map(|(series, value)| {
+                let key = metric_label(series, "agent").unwrap_or("unknown").to_string();
+                (key, *value)
+            })
+            .collect();
+
+        if scores.is_empty() {
+            return Err(WakuError::Failed(format!(
+                "Node {} does not export {}; gossipsub scoring metrics are unavailable",
+                node.name, GOSSIPSUB_PEER_SCORE_METRIC
+            )));
+        }
+
+        debug!("Node {} gossipsub peer scores: {:?}", node.name, scores);
+        Ok(scores)
+    }
+
+    pub async fn container_config(&self, node: &WakuNode) -> Result<ContainerConfigDump> {
+        let _permit = self.docker_permit().await?;
+        let inspect = self.docker
+            .inspect_container(&node.container_id, None)
+            .await
+            .context("Failed to inspect container")?;
+
+        let config = inspect.config.unwrap_or_default();
+
+        Ok(ContainerConfigDump {
+            image: config.image,
+            cmd: config.cmd.unwrap_or_default(),
+            env: config.env.unwrap_or_default(),
+            host_config: inspect.host_config,
+        })
+    }
+
+    pub async fn assert_configs_consistent(&self, nodes: &[WakuNode], ignore_fields: &[&str]) -> Result<()> {
+        let Some((reference, rest)) = nodes.split_first() else {
+            return Ok(());
+        };
+
+        let flags = |cmd: &[String]| -> HashMap<String, Vec<String>> {
+            let mut flags: HashMap<String, Vec<String>> = HashMap::new();
+            for arg in cmd {
+                let arg = arg.trim_start_matches('-');
+                let (name, value) = arg.split_once('=').unwrap_or((arg, ""));
+                if !ignore_fields.contains(&name) {
+                    flags.entry(name.to_string()).or_default().push(value.to_string());
+                }
+            }
+            flags
+        };
+
+        let expected = flags(&self.container_config(reference).await?.cmd);
+        let mut differences = Vec::new();
+
+        for node in rest {
+            let actual = flags(&self.container_config(node).await?.cmd);
+            let names: BTreeSet<&String> = expected.keys().chain(actual.keys()).collect();
+
+            for name in names {
+                let (a, b) = (expected.get(name), actual.get(name));
+                if a != b {
+                    differences.push(format!(
+                        "{}: --{} is {:?} on {} but {:?} on {}",
+                        node.name, name, a, reference.name, b, node.name
+                    ));
+                }
+            }
+        }
+
+        if !differences.is_empty() {
+            return Err(WakuError::Failed(format!(
+                "Node configs differ from {}:\n{}",
+                reference.name, differences.join("\n")
+            )));
+        }
+
+        info!("{} nodes share a consistent config", nodes.len());
+        Ok(())
+    }
+
+    pub async fn get_container_logs(
+        &self,
+        node: &WakuNode,
+        tail: Option<usize>,
+        since: Option<SystemTime>,
+    ) -> Result<String> {
+        let since = match since {
+            Some(time) => time
+                .duration_since(UNIX_EPOCH)
+                .context("Log start time is before the Unix epoch")?
+                .as_secs() as i64,
+            None => 0,
+        };
+
+        let options = LogsOptions::<String> {
+            stdout: true,
+            stderr: true,
+            since,
+            tail: tail.map(|n| n.to_string()).unwrap_or_else(|| "all".to_string()),
+            ..Default::default()
+        };
+
+        let _permit = self.docker_permit().await?;
+        let mut stream = Box::pin(self.docker.logs(&node.container_id, Some(options)));
+        let mut logs = String::new();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.context("Failed to read container logs")?;
+            logs.push_str(&chunk.to_string());
+        }
+
+        Ok(logs)
+    }
+
+    pub async fn wait_for_full_mesh(&self, nodes: &[WakuNode], timeout: Duration) -> Result<bool> {
+        let mut peer_ids = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            let info = self.get_node_info(node).await?;
+            let peer_id = peer_id_from_info(&info)
+                .with_context(|| format!("Could not determine peer ID of node {}", node.name))?;
+            peer_ids.push(peer_id);
+        }
+
+        let start = Instant::now();
+        loop {
+            let mut missing = 0;
+            for (i, node) in nodes.iter().enumerate() {
+                let connected: BTreeSet<String> = self.get_peers(node).await?
+                    .into_iter()
+                    .filter(|p| p.connected)
+                    .map(|p| p.peer_id)
+                    .collect();
+
+                missing += peer_ids
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, id)| *j != i && !connected.contains(*id))
+                    .count();
+            }
+
+            if missing == 0 {
+                info!("All {} nodes are connected to each other", nodes.len());
+                return Ok(true);
+            }
+
+            if start.elapsed() >= timeout {
+                warn!("Full mesh not formed within {:?}, {} links missing", timeout, missing);
+                return Ok(false);
+            }
+            debug!("Waiting for full mesh, {} links missing", missing);
+            sleep(Duration::from_secs(2)).await;
+        }
+    }
+
+    pub async fn wait_for_mesh_stable(&self, node: &WakuNode, stable_duration: Duration, timeout: Duration) -> Result<()> {
+        let start = Instant::now();
+        let mut count = self.connected_peer_count(node).await?;
+        let mut stable_since = Instant::now();
+
+        loop {
+            if stable_since.elapsed() >= stable_duration {
+                info!("Node {} mesh stable at {} peers for {:?}", node.name, count, stable_duration);
+                return Ok(());
+            }
+
+            if start.elapsed() >= timeout {
+                return Err(WakuError::Timeout(format!(
+                    "Node {} peer count did not settle for {:?} within {:?}",
+                    node.name, stable_duration, timeout
+                )));
+            }
+
+            sleep(Duration::from_millis(500)).await;
+
+            let current = self.connected_peer_count(node).await?;
+            if current != count {
+                debug!("Node {} peer count changed {} -> {}", node.name, count, current);
+                count = current;
+                stable_since = Instant::now();
+            }
+        }
+    }
+
+    pub async fn exec_in_container(&self, node: &WakuNode, cmd: Vec<String>) -> Result<ExecOutput> {
+        debug!("Executing in container {}: {:?}", node.name, cmd);
+        let _permit = self.docker_permit().await?;
+
+        let exec = self.docker
+            .create_exec(&node.container_id, CreateExecOptions {
+                cmd: Some(cmd),
+                attach_stdout: Some(true),
+                attach_stderr: Some(true),
+                ..Default::default()
+            })
+            .await
+            .context("Failed to create exec")?;
+
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+        if let StartExecResults::Attached { mut output, .. } = self.docker
+            .start_exec(&exec.id, None)
+            .await
+            .context("Failed to start exec")?
+        {
+            while let Some(chunk) = output.next().await {
+                match chunk.context("Failed to read exec output")? {
+                    LogOutput::StdOut { message } => stdout.extend_from_slice(&message),
+                    LogOutput::StdErr { message } => stderr.extend_from_slice(&message),
+                    _ => {}
+                }
+            }
+        }
+
+        let inspect = self.docker
+            .inspect_exec(&exec.id)
+            .await
+            .context("Failed to inspect exec")?;
+
+        Ok(ExecOutput {
+            exit_code: inspect.exit_code.unwrap_or(-1),
+            stdout: String::from_utf8_lossy(&stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&stderr).into_owned(),
+        })
+    }
+
+    pub async fn generate_rln_credentials(&self, node: &WakuNode, output_path: &str) -> Result<()> {
+        let rln = node.rln.as_ref()
+            .with_context(|| format!("Node {} was started without an RLN config", node.name))?;
+
+        // output_path is relative to the mounted keystore dir so the file is visible on the host
+        let cmd = vec![
+            "/usr/bin/wakunode".to_string(),
+            "generateRlnKeystore".to_string(),
+            format!("--rln-relay-eth-client-address={}", rln.eth_client_address),
+            format!("--rln-relay-eth-private-key={}", rln.eth_private_key),
+            format!("--rln-relay-eth-contract-address={}", rln.contract_address),
+            format!("--rln-relay-cred-path={}/{}", RLN_KEYSTORE_MOUNT, output_path),
+            format!("--rln-relay-cred-password={}", rln.keystore_password),
+        ];
+
+        info!("Generating RLN credentials on node {} at {}", node.name, output_path);
+        let output = self.exec_in_container(node, cmd).await?;
+
+        if output.exit_code != 0 {
+            return Err(WakuError::Failed(format!(
+                "RLN credential generation on {} exited with {}: {}",
+                node.name, output.exit_code, output.stderr.trim()
+            )));
+        }
+
+        let host_path = rln.keystore_dir.join(output_path);
+        if !host_path.exists() {
+            return Err(WakuError::Failed(format!(
+                "RLN credential generation succeeded but {} was not written", host_path.display()
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn rest_request(
+        &self,
+        node: &WakuNode,
+        method: Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<RestResponse> {
+        self.http_request(node, node.rest_port, method, path, body).await
+    }
+
+    async fn http_request(
+        &self,
+        node: &WakuNode,
+        port: u16,
+        method: Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<RestResponse> {
+        let url = format!("http://127.0.0.1:{}{}", port, path);
+
+        if self.options.rest_access == RestAccess::DockerExec {
+            return self.exec_http_request(node, method, &url, body).await;
+        }
+
+        let mut request = self.client.request(method, &url);
+        if let Some(body) = &body {
+            request = request.header("Content-Type", "application/json").json(body);
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.text().await
+            .context("Failed to read response body")?;
+
+        Ok(RestResponse { status, headers, body })
+    }
+
+    async fn exec_http_request(
+        &self,
+        node: &WakuNode,
+        method: Method,
+        url: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<RestResponse> {
+        // Headers are dumped to stderr so stdout stays the body plus the -w status line
+        let mut cmd = vec![
+            "curl".to_string(),
+            "-s".to_string(),
+            "--max-time".to_string(),
+            REST_TIMEOUT.as_secs().to_string(),
+            "-D".to_string(),
+            "/dev/stderr".to_string(),
+            "-X".to_string(),
+            method.as_str().to_string(),
+            "-w".to_string(),
+            "\n%{http_code}".to_string(),
+        ];
+
+        for (name, value) in &self.options.default_headers {
+            if let Ok(value) = value.to_str() {
+                cmd.push("-H".to_string());
+                cmd.push(format!("{}: {}", name.as_str(), value));
+            }
+        }
+
+        if let Some(body) = &body {
+            cmd.push("-H".to_string());
+            cmd.push("Content-Type: application/json".to_string());
+            cmd.push("--data-binary".to_string());
+            cmd.push(body.to_string());
+        }
+        cmd.push(url.to_string());
+
+        let output = self.exec_in_container(node, cmd).await?;
+        // 126/127: curl isn't in the image; alpine-based images still have busybox wget
+        if matches!(output.exit_code, 126 | 127) {
+            debug!("curl not available in container {}, falling back to wget", node.name);
+            return self.exec_wget_request(node, method, url, body).await;
+        }
+        if output.exit_code != 0 {
+            return Err(WakuError::Failed(format!(
+                "curl in container {} exited with {}: {}",
+                node.name, output.exit_code, output.stderr.trim()
+            )));
+        }
+
+        // The status code is appended on its own line by -w
+        let (body, status) = output.stdout
+            .rsplit_once('\n')
+            .context("Malformed curl output")?;
+        let status: u16 = status.trim().parse().context("Malformed curl status code")?;
+
+        Ok(RestResponse {
+            status: StatusCode::from_u16(status)
+                .map_err(|e| WakuError::ParseResponse(format!("Invalid HTTP status code {}: {}", status, e)))?,
+            headers: dumped_headers(&output.stderr),
+            body: body.to_string(),
+        })
+    }
+
+    async fn exec_wget_request(
+        &self,
+        node: &WakuNode,
+        method: Method,
+        url: &str,
+        body: Option<serde_json::Value>,
+    ) -> Result<RestResponse> {
+        // -S prints the status line and headers to stderr, including for error responses
+        let mut cmd = vec![
+            "wget".to_string(),
+            "-q".to_string(),
+            "-S".to_string(),
+            "-T".to_string(),
+            REST_TIMEOUT.as_secs().to_string(),
+            "-O".to_string(),
+            "-".to_string(),
+        ];
+
+        for (name, value) in &self.options.default_headers {
+            if let Ok(value) = value.to_str() {
+                cmd.push("--header".to_string());
+                cmd.push(format!("{}: {}", name.as_str(), value));
+            }
+        }
+
+        // busybox wget can only GET, or POST via --post-data
+        match method {
+            Method::GET if body.is_none() => {}
+            Method::POST => {
+                cmd.push("--header".to_string());
+                cmd.push("Content-Type: application/json".to_string());
+                cmd.push("--post-data".to_string());
+                cmd.push(body.map(|b| b.to_string()).unwrap_or_default());
+            }
+            _ => {
+                return Err(WakuError::Failed(format!(
+                    "{} requests need curl, which container {} does not have", method, node.name
+                )));
+            }
+        }
+        cmd.push(url.to_string());
+
+        let output = self.exec_in_container(node, cmd).await?;
+        let status = wget_status(&output.stderr).with_context(|| format!(
+            "wget in container {} exited with {}: {}",
+            node.name, output.exit_code, output.stderr.trim()
+        ))?;
+
+        Ok(RestResponse {
+            status: StatusCode::from_u16(status)
+                .map_err(|e| WakuError::ParseResponse(format!("Invalid HTTP status code {}: {}", status, e)))?,
+            headers: dumped_headers(&output.stderr),
+            // wget drops the body of error responses
+            body: output.stdout,
+        })
+    }
+
+    pub async fn unreachable_bootstrap_enr(&self, config: WakuNodeConfig) -> Result<String> {
+        // A real ENR whose node is gone: well-formed, but nothing answers on its address
+        let node = self.start_waku_node(config).await?;
+        let enr = self.get_node_info(&node).await.map(|info| info.enr_uri);
+        self.cleanup_node(&node).await?;
+
+        let enr = enr?;
+        info!("Using {} as an unreachable bootstrap node", enr);
+        Ok(enr)
+    }
+
+    pub async fn assert_ready_with_unreachable_bootstrap(
+        &self,
+        mut config: WakuNodeConfig,
+        dead_enr: &str,
+        timeout: Duration,
+    ) -> Result<WakuNode> {
+        config.bootstrap_node = Some(dead_enr.to_string());
+        config.readiness_timeout = config.readiness_timeout.min(timeout);
+        let topic = random_content_topic("dead-bootstrap");
+
+        // Started outside the timed block: a timeout dropping the future mid-start would leak the container
+        let started = Instant::now();
+        let node = self.start_waku_node(config).await?;
+
+        let ready = async {
+            self.get_node_info(&node).await?;
+
+            self.subscribe_to_topic(&node, &topic).await?;
+            let message = create_test_message("dead-bootstrap-check", &topic);
+            self.publish_message(&node, &message).await?;
+            self.wait_for_message(&node, &topic, PROPAGATION_TIMEOUT, |m| m.payload == message.payload)
+                .await?
+                .context("Node with an unreachable bootstrap could not relay to itself")?;
+
+            Ok::<_, WakuError>(())
+        };
+
+        let remaining = timeout.saturating_sub(started.elapsed());
+        let result = match tokio::time::timeout(remaining, ready).await {
+            Ok(result) => result,
+            Err(_) => Err(WakuError::NodeNotReady(format!(
+                "Node with an unreachable bootstrap was not ready within {:?}", timeout
+            ))),
+        };
+        if let Err(e) = result {
+            if let Err(cleanup) = self.cleanup_node(&node).await {
+                warn!("Failed to clean up node {} after readiness failure: {}", node.name, cleanup);
+            }
+            return Err(e);
+        }
+
+        info!("Node {} is ready despite unreachable bootstrap", node.name);
+        Ok(node)
+    }
+
+    pub async fn run_smoke_test(&self, config: WakuNodeConfig) -> Result<SmokeReport> {
+        let mut report = SmokeReport::default();
+        let topic = "/my-app/2/chatroom-1/proto";
+
+        let started = Instant::now();
+        let node = self.start_waku_node(config).await;
+        report.record("start node", started, &node);
+        let node = match node {
+            Ok(node) => node,
+            Err(_) => return Ok(report),
+        };
+
+        let steps = async {
+            let started = Instant::now();
+            let node_info = self.get_node_info(&node).await;
+            report.record("get node info", started, &node_info);
+            if let Ok(node_info) = &node_info {
+                info!("Node ENR: {}", node_info.enr_uri);
+            }
+            node_info?;
+
+            let started = Instant::now();
+            let subscribed = self.subscribe_to_topic(&node, topic).await;
+            report.record("subscribe", started, &subscribed);
+            subscribed?;
+
+            let message = create_test_message("Test message", topic);
+            let started = Instant::now();
+            let published = self.publish_message(&node, &message).await;
+            report.record("publish", started, &published);
+            published?;
+
+            let started = Instant::now();
+            let received = self
+                .wait_for_message(&node, topic, PROPAGATION_TIMEOUT, |m| m.payload == message.payload)
+                .await
+                .and_then(|m| m.context("Published message was not received"));
+            report.record("receive", started, &received);
+            received?;
+
+            Ok::<_, WakuError>(())
+        };
+        let _ = steps.await;
+
+        let started = Instant::now();
+        let cleaned = self.cleanup_node(&node).await;
+        report.record("cleanup", started, &cleaned);
+
+        Ok(report)
+    }
+
+    pub async fn discover_nodes(&self) -> Result<Vec<WakuNode>> {
+        use bollard::container::ListContainersOptions;
+
+        let options = Some(ListContainersOptions::<String> {
+            filters: HashMap::from([("label".to_string(), vec![NODE_LABEL.to_string()])]),
+            ..Default::default()
+        });
+
+        let containers = self.docker.list_containers(options).await
+            .context("Failed to list waku containers")?;
+
+        let mut nodes = Vec::new();
+        for id in containers.into_iter().filter_map(|container| container.id) {
+            let inspect = self.docker
+                .inspect_container(&id, None)
+                .await
+                .context("Failed to inspect container")?;
+
+            let cmd = inspect.config.and_then(|config| config.cmd).unwrap_or_default();
+            let ports = inspect.network_settings.and_then(|settings| settings.ports).unwrap_or_default();
+
+            // Commands carry container ports; resolve them to the host ports they're published on
+            let port = |flag: &str, protocol: &str| -> Option<u16> {
+                let container_port: u16 = flag_value(&cmd, flag)?.parse().ok()?;
+                let host_port = ports
+                    .get(&format!("{}/{}", container_port, protocol))
+                    .and_then(|bindings| bindings.as_ref()?.first()?.host_port.as_ref()?.parse().ok());
+                Some(host_port.unwrap_or(container_port))
+            };
+
+            let store_db_dir = inspect.host_config
+                .and_then(|host_config| host_config.binds)
+                .unwrap_or_default()
+                .iter()
+                .find_map(|bind| {
+                    let (host, container) = bind.split_once(':')?;
+                    container.starts_with(STORE_DB_MOUNT).then(|| PathBuf::from(host))
+                });
+
+            let mut node = WakuNode {
+                container_id: id.clone(),
+                name: inspect.name.unwrap_or_default().trim_start_matches('/').to_string(),
+                rest_port: port("rest-port", "tcp").unwrap_or_default(),
+                tcp_port: port("tcp-port", "tcp").or_else(|| port("quic-port", "udp")).unwrap_or_default(),
+                websocket_port: port("websocket-port", "tcp").unwrap_or_default(),
+                discv5_port: port("discv5-udp-port", "udp").unwrap_or_default(),
+                external_ip: flag_value(&cmd, "nat")
+                    .and_then(|nat| nat.strip_prefix("extip:"))
+                    .unwrap_or_default()
+                    .to_string(),
+                enr_uri: None,
+                store_db_dir,
+                metrics_port: port("metrics-server-port", "tcp"),
+                hostname: None,
+                rln: None,
+                max_msg_size_kib: flag_value(&cmd, "max-msg-size")
+                    .and_then(|size| size.strip_suffix("KiB")?.parse().ok())
+                    .unwrap_or(DEFAULT_MAX_MSG_SIZE_KIB),
+            };
+
+            match self.get_node_info(&node).await {
+                Ok(info) => node.enr_uri = Some(info.enr_uri),
+                Err(e) => warn!("Could not read ENR of discovered node {}: {}", node.name, e),
+            }
+
+            nodes.push(node);
+        }
+
+        info!("Discovered {} running waku nodes", nodes.len());
+        Ok(nodes)
+    }
+
+    pub async fn cleanup_existing_containers(&self) -> Result<()> {
+        use bollard::container::ListContainersOptions;
+        
+        let options = Some(ListContainersOptions::<String> {
+            all: true,
+            filters: {
+                let mut filters = std::collections::HashMap::new();
+                let name_filter = if self.options.name_prefix.is_empty() {
+                    "waku-node".to_string()
+                } else {
+                    self.options.name_prefix.clone()
+                };
+                filters.insert("name".to_string(), vec![name_filter]);
+                filters
+            },
+            ..Default::default()
+        });
+
+        let containers = self.docker.list_containers(options).await?;
+        
+        for container in containers {
+            if let Some(id) = container.id {
+                info!("Cleaning up existing container: {}", id);
+                let _ = self.docker.stop_container(&id, None).await;
+                let _ = self.docker.remove_container(&id, None).await;
+            }
+        }
+        
+        Ok(())
+    }
+
+    pub async fn cleanup_node(&self, node: &WakuNode) -> Result<()> {
+        self.cleanup_node_with_timeout(node, None).await
+    }
+
+    // stop_timeout is the grace period in seconds before SIGKILL; None uses Docker's default
+    pub async fn cleanup_node_with_timeout(&self, node: &WakuNode, stop_timeout: Option<i64>) -> Result<()> {
+        info!("Cleaning up node: {}", node.name);
+        
+        // Stop and remove container; 304 means already stopped and 404 already removed
+        let options = stop_timeout.map(|t| StopContainerOptions { t });
+        match self.docker.stop_container(&node.container_id, options).await {
+            Ok(_) => {}
+            Err(bollard::errors::Error::DockerResponseServerError { status_code: 304 | 404, .. }) => {
+                debug!("Container {} was already stopped", node.container_id);
+            }
+            Err(e) => warn!("Failed to stop container {}: {}", node.container_id, e),
+        }
+        
+        match self.docker.remove_container(&node.container_id, None).await {
+            Ok(_) => {}
+            Err(bollard::errors::Error::DockerResponseServerError { status_code: 404, .. }) => {
+                debug!("Container {} was already removed", node.container_id);
+            }
+            Err(e) => warn!("Failed to remove container {}: {}", node.container_id, e),
+        }
+
+        self.wait_for_container_removed(&node.container_id, REMOVAL_TIMEOUT).await
+    }
+
+    pub async fn wait_for_exit(&self, node: &WakuNode, timeout: Duration) -> Result<i64> {
+        let options = WaitContainerOptions { condition: "not-running" };
+        let mut stream = Box::pin(self.docker.wait_container(&node.container_id, Some(options)));
+
+        let result = tokio::time::timeout(timeout, stream.next())
+            .await
+            .map_err(|_| WakuError::Timeout(format!("Node {} did not exit within {:?}", node.name, timeout)))?
+            .with_context(|| format!("Wait stream for node {} ended without a result", node.name))?;
+
+        // bollard reports a non-zero exit status as an error rather than a response
+        let code = match result {
+            Ok(response) => response.status_code,
+            Err(bollard::errors::Error::DockerContainerWaitError { code, .. }) => code,
+            Err(e) => return Err(e).context("Failed to wait for container"),
+        };
+
+        info!("Node {} exited with code {}", node.name, code);
+        Ok(code)
+    }
+
+    pub async fn cleanup_unless_failed(&self, nodes: &[WakuNode], test_failed: bool) -> Result<()> {
+        if test_failed && self.options.keep_on_failure {
+            let names: Vec<&str> = nodes.iter().map(|node| node.name.as_str()).collect();
+            warn!("Test failed; leaving containers running for investigation: {}", names.join(", "));
+            return Ok(());
+        }
+
+        for node in nodes {
+            self.cleanup_node(node).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn wait_for_container_removed(&self, container_id: &str, timeout: Duration) -> Result<()> {
+        let start = Instant::now();
+
+        loop {
+            match self.docker.inspect_container(container_id, None).await {
+                Err(bollard::errors::Error::DockerResponseServerError { status_code: 404, .. }) => {
+                    debug!("Container {} removed", container_id);
+                    return Ok(());
+                }
+                Err(e) => return Err(e).context("Failed to inspect container during removal"),
+                Ok(_) => {}
+            }
+
+            if start.elapsed() >= timeout {
+                return Err(WakuError::Timeout(format!(
+                    "Container {} was not removed within {:?}",
+                    container_id, timeout
+                )));
+            }
+            sleep(Duration::from_millis(200)).await;
+        }
+    }
+
+    pub async fn cleanup_network(&self) -> Result<()> {
+        self.remove_network(&self.network_name).await
+    }
+
+    pub async fn remove_network(&self, name: &str) -> Result<()> {
+        info!("Cleaning up network: {}", name);
+        
+        if let Err(e) = self.docker.remove_network(name).await {
+            warn!("Failed to remove network {}: {}", name, e);
+        }
+        
+        Ok(())
+    }
+}
+
+// Removes its container when dropped, so a panicking test doesn't leak it
+pub struct ScopedWakuNode {
+    node: Option<WakuNode>,
+}
+
+impl ScopedWakuNode {
+    pub fn into_inner(mut self) -> WakuNode {
+        self.node.take().expect("ScopedWakuNode holds a node until dropped")
+    }
+}
+
+impl std::ops::Deref for ScopedWakuNode {
+    type Target = WakuNode;
+
+    fn deref(&self) -> &WakuNode {
+        self.node.as_ref().expect("ScopedWakuNode holds a node until dropped")
+    }
+}
+
+impl Drop for ScopedWakuNode {
+    fn drop(&mut self) {
+        let Some(node) = self.node.take() else {
+            return;
+        };
+
+        // Drop can't be async and may run inside a runtime, so clean up on a
+        // separate thread with its own runtime and Docker connection
+        let cleanup = std::thread::spawn(move || -> Result<()> {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+            runtime.block_on(async {
+                let docker = Docker::connect_with_local_defaults().map_err(WakuError::DockerConnect)?;
+                let options = RemoveContainerOptions { force: true, ..Default::default() };
+                match docker.remove_container(&node.container_id, Some(options)).await {
+                    Ok(_) | Err(bollard::errors::Error::DockerResponseServerError { status_code: 404, .. }) => {
+                        debug!("Removed scoped node {}", node.name);
+                    }
+                    Err(e) => return Err(e).context("Failed to remove container"),
+                }
+                Ok::<_, WakuError>(())
+            })
+        });
+
+        match cleanup.join() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!("Failed to remove scoped node container: {}", e),
+            Err(_) => warn!("Scoped node cleanup thread panicked"),
+        }
+    }
+}
+
+pub struct SlowConsumer {
+    framework: WakuTestFramework,
+    node: WakuNode,
+    topic: String,
+    interval: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct SlowConsumerReport {
+    pub polls: usize,
+    pub messages: Vec<ReceivedMessage>,
+}
+
+impl SlowConsumerReport {
+    pub fn seen(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn missed(&self, published: usize) -> usize {
+        published.saturating_sub(self.seen())
+    }
+}
+
+impl SlowConsumer {
+    pub async fn subscribe(
+        framework: &WakuTestFramework,
+        node: &WakuNode,
+        topic: &str,
+        interval: Duration,
+    ) -> Result<Self> {
+        framework.subscribe_to_topic(node, topic).await?;
+
+        Ok(Self {
+            framework: framework.clone(),
+            node: node.clone(),
+            topic: topic.to_string(),
+            interval,
+        })
+    }
+
+    pub async fn consume_for(&self, duration: Duration) -> Result<SlowConsumerReport> {
+        let start = Instant::now();
+        let mut report = SlowConsumerReport { polls: 0, messages: Vec::new() };
+
+        while start.elapsed() < duration {
+            sleep(self.interval).await;
+
+            let messages = self.framework.get_messages(&self.node, &self.topic).await?;
+            debug!("Slow consumer on node {} drained {} messages", self.node.name, messages.len());
+            report.polls += 1;
+            report.messages.extend(messages);
+        }
+
+        info!("Slow consumer on node {} saw {} messages in {} polls", self.node.name, report.seen(), report.polls);
+        Ok(report)
+    }
+}
+
+const SEQUENCE_PREFIX: &str = "seq:";
+
+pub struct SequencedPublisher {
+    framework: WakuTestFramework,
+    node: WakuNode,
+    topic: String,
+    next: u64,
+}
+
+impl SequencedPublisher {
+    pub fn new(framework: &WakuTestFramework, node: &WakuNode, topic: &str) -> Self {
+        Self {
+            framework: framework.clone(),
+            node: node.clone(),
+            topic: topic.to_string(),
+            next: 0,
+        }
+    }
+
+    pub fn published(&self) -> u64 {
+        self.next
+    }
+
+    pub async fn publish_next(&mut self) -> Result<u64> {
+        let seq = self.next;
+        let message = create_test_message(&format!("{}{}", SEQUENCE_PREFIX, seq), &self.topic);
+
+        self.framework.publish_message(&self.node, &message).await?;
+        self.next += 1;
+
+        Ok(seq)
+    }
+
+    pub async fn publish_at_rate(&mut self, count: u64, interval: Duration) -> Result<()> {
+        let mut ticker = tokio::time::interval(interval);
+
+        for _ in 0..count {
+            ticker.tick().await;
+            self.publish_next().await?;
+        }
+
+        info!("Published {} sequenced messages from node {}", count, self.node.name);
+        Ok(())
+    }
+}
+
+pub fn sequence_number(message: &ReceivedMessage) -> Option<u64> {
+    let payload = String::from_utf8(message.decoded_payload().ok()?).ok()?;
+    payload.strip_prefix(SEQUENCE_PREFIX)?.parse().ok()
+}
+
+// published is SequencedPublisher::published(), so trailing losses count as gaps too
+pub fn detect_gaps(messages: &[ReceivedMessage], published: u64) -> Vec<u64> {
+    let seen: BTreeSet<u64> = messages.iter().filter_map(sequence_number).collect();
+
+    (0..published).filter(|seq| !seen.contains(seq)).collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct WakuNodeConfig {
+    pub name: String,
+    pub rest_port: u16,
+    pub tcp_port: u16,
+    pub websocket_port: u16,
+    pub discv5_port: u16,
+    pub external_ip: String,
+    // Convenience for the single-bootstrap case; combined with bootstrap_nodes
+    pub bootstrap_node: Option<String>,
+    pub bootstrap_nodes: Vec<String>,
+    pub static_nodes: Vec<String>,
+    pub pubsub_topics: Vec<String>,
+    pub relay: bool,
+    pub store: bool,
+    pub relay_cache_capacity: u32,
+    pub rest_allow_origins: Vec<String>,
+    pub store_db_dir: Option<PathBuf>,
+    pub websocket_secure: Option<WebsocketSecureConfig>,
+    pub max_connections: u32,
+    pub extra_args: Vec<String>,
+    pub dns_servers: Vec<String>,
+    pub peer_exchange_node: Option<String>,
+    pub metrics_port: Option<u16>,
+    pub hostname: Option<String>,
+    pub discv5: bool,
+    pub peer_exchange: bool,
+    pub log_max_size: Option<String>,
+    pub log_max_files: Option<u32>,
+    pub transport: Transport,
+    pub rln: Option<RlnConfig>,
+    // (name, soft, hard), e.g. ("nofile", 65536, 65536)
+    pub ulimits: Vec<(String, i64, i64)>,
+    // Scopes the autosharded shards the node subscribes to; nwaku has no per-content-topic allowlist
+    pub content_topics: Vec<String>,
+    pub agent_string: Option<String>,
+    // Only applies when bootstrap or static nodes are set
+    pub require_peer_on_start: bool,
+    pub store_retention_time_secs: Option<u64>,
+    pub readiness_timeout: Duration,
+    pub image: String,
+    pub auto_pull: bool,
+    pub cluster_id: Option<u16>,
+    pub shards: Vec<u16>,
+    pub max_msg_size_kib: Option<u32>,
+    // nwaku pings its peers so idle connections aren't reaped during quiet periods
+    pub keep_alive: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Transport {
+    #[default]
+    Tcp,
+    Quic,
+}
+
+impl Transport {
+    pub fn protocol(self) -> &'static str {
+        match self {
+            Transport::Tcp => "tcp",
+            Transport::Quic => "udp",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WebsocketSecureConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct RlnConfig {
+    pub keystore_dir: PathBuf,
+    pub keystore_password: String,
+    pub eth_client_address: String,
+    pub eth_private_key: String,
+    pub contract_address: String,
+}
+
+impl Default for WakuNodeConfig {
+    fn default() -> Self {
+        Self {
+            name: "waku-node".to_string(),
+            rest_port: 22161, // Changed from 21161 to avoid conflicts
+            tcp_port: 22162,
+            websocket_port: 22163,
+            discv5_port: 22164,
+            external_ip: "172.18.111.226".to_string(),
+            bootstrap_node: None,
+            bootstrap_nodes: Vec::new(),
+            static_nodes: Vec::new(),
+            pubsub_topics: Vec::new(),
+            relay: true,
+            store: false,
+            relay_cache_capacity: 100,
+            rest_allow_origins: Vec::new(),
+            store_db_dir: None,
+            websocket_secure: None,
+            max_connections: 50,
+            extra_args: Vec::new(),
+            dns_servers: Vec::new(),
+            peer_exchange_node: None,
+            metrics_port: None,
+            hostname: None,
+            discv5: true,
+            peer_exchange: true,
+            log_max_size: None,
+            log_max_files: None,
+            transport: Transport::Tcp,
+            rln: None,
+            ulimits: Vec::new(),
+            content_topics: Vec::new(),
+            agent_string: None,
+            require_peer_on_start: false,
+            store_retention_time_secs: None,
+            readiness_timeout: Duration::from_secs(30),
+            image: DEFAULT_IMAGE.to_string(),
+            auto_pull: true,
+            cluster_id: None,
+            shards: Vec::new(),
+            max_msg_size_kib: None,
+            keep_alive: false,
+        }
+    }
+}
+
+fn peer_id_from_info(info: &NodeInfo) -> Option<String> {
+    info.listen_addresses
+        .iter()
+        .find_map(|addr| addr.split("/p2p/").nth(1))
+        .map(|id| id.trim_end_matches('/').to_string())
+}
+
+fn tcp_multiaddr(info: &NodeInfo, node: &WakuNode) -> Option<String> {
+    let tcp_segment = format!("/tcp/{}/", node.tcp_port);
+
+    info.listen_addresses
+        .iter()
+        .filter(|addr| addr.contains(&tcp_segment) && !addr.contains("/ws"))
+        .find(|addr| addr.contains(&node.external_ip))
+        .or_else(|| info.listen_addresses.iter().find(|addr| addr.contains(&tcp_segment) && !addr.contains("/ws")))
+        .cloned()
+}
+
+fn encode_query(params: &[(&str, String)]) -> String {
+    params
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, urlencoding::encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn parse_health(status: StatusCode, body: &str) -> HealthReport {
+    // Older nwaku answers with plain text; newer versions report per-protocol state as JSON
+    let Ok(json) = serde_json::from_str::<serde_json::Value>(body) else {
+        return HealthReport {
+            healthy: status.is_success(),
+            status: body.trim().to_string(),
+            protocols: HashMap::new(),
+        };
+    };
+
+    let overall = json["nodeHealth"].as_str().unwrap_or_default().to_string();
+    let mut protocols = HashMap::new();
+    let entries = match &json["protocolsHealth"] {
+        serde_json::Value::Array(entries) => entries.iter().filter_map(|e| e.as_object()).collect(),
+        serde_json::Value::Object(entries) => vec![entries],
+        _ => Vec::new(),
+    };
+    for entry in entries {
+        for (protocol, state) in entry {
+            protocols.insert(protocol.clone(), state.as_str().unwrap_or_default().to_string());
+        }
+    }
+
+    HealthReport {
+        healthy: status.is_success() && overall.eq_ignore_ascii_case("ready"),
+        status: overall,
+        protocols,
+    }
+}
+
+// Store v3 wraps each message as {messageHash, message, pubsubTopic}; older APIs return bare messages
+fn parse_store_messages(body: &serde_json::Value) -> Result<Vec<ReceivedMessage>> {
+    let entries = match body.get("messages").and_then(|m| m.as_array()) {
+        Some(entries) => entries,
+        None => return Ok(Vec::new()),
+    };
+
+    entries
+        .iter()
+        .map(|entry| {
+            let mut message = entry.get("message").cloned().unwrap_or_else(|| entry.clone());
+            if let (Some(hash), Some(fields)) = (entry.get("messageHash"), message.as_object_mut()) {
+                fields.entry("messageHash").or_insert_with(|| hash.clone());
+            }
+            serde_json::from_value(message).context("Failed to parse store message")
+        })
+        .collect()
+}
+
+fn parse_store_cursor(body: &serde_json::Value) -> Option<String> {
+    ["paginationCursor", "cursor"]
+        .iter()
+        .filter_map(|key| body.get(key))
+        .find_map(|cursor| match cursor {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(s) if s.is_empty() => None,
+            serde_json::Value::String(s) => Some(s.clone()),
+            other => Some(other.to_string()),
+        })
+}
+
+pub async fn retry_with_backoff<T, F, Fut>(max_attempts: u32, base_delay: Duration, operation: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let policy = RetryPolicy {
+        max_attempts,
+        initial_delay: base_delay,
+        max_delay: Duration::MAX,
+        ..Default::default()
+    };
+    policy.run(operation).await
+}
+
+fn is_transient_docker_error(error: &bollard::errors::Error) -> bool {
+    match error {
+        bollard::errors::Error::DockerResponseServerError { status_code, .. } => *status_code >= 500,
+        _ => true,
+    }
+}
+
+const SERIES_PORT_STRIDE: u16 = 10;
+
+impl WakuNodeConfig {
+    /// Default config with host ports picked by the OS instead of the fixed defaults.
+    ///
+    /// The probe sockets are closed before Docker binds the ports, so another process
+    /// can take one in between; the window is small but not zero.
+    pub fn with_dynamic_ports() -> Result<Self> {
+        let mut config = Self::default();
+        config.assign_dynamic_ports()?;
+        Ok(config)
+    }
+
+    fn assign_dynamic_ports(&mut self) -> Result<()> {
+        use std::net::{TcpListener, UdpSocket};
+
+        // Keep every socket open until all ports are picked so none are handed out twice
+        let rest = TcpListener::bind("0.0.0.0:0").context("Failed to allocate REST port")?;
+        let tcp = TcpListener::bind("0.0.0.0:0").context("Failed to allocate TCP port")?;
+        let quic = match self.transport {
+            Transport::Quic => Some(UdpSocket::bind("0.0.0.0:0").context("Failed to allocate QUIC port")?),
+            Transport::Tcp => None,
+        };
+        let websocket = TcpListener::bind("0.0.0.0:0").context("Failed to allocate websocket port")?;
+        let discv5 = UdpSocket::bind("0.0.0.0:0").context("Failed to allocate discv5 port")?;
+        let metrics = match self.metrics_port {
+            Some(_) => Some(TcpListener::bind("0.0.0.0:0").context("Failed to allocate metrics port")?),
+            None => None,
+        };
+
+        self.rest_port = rest.local_addr()?.port();
+        self.tcp_port = match &quic {
+            Some(quic) => quic.local_addr()?.port(),
+            None => tcp.local_addr()?.port(),
+        };
+        self.websocket_port = websocket.local_addr()?.port();
+        self.discv5_port = discv5.local_addr()?.port();
+        if let Some(metrics) = metrics {
+            self.metrics_port = Some(metrics.local_addr()?.port());
+        }
+
+        debug!(
+            "Allocated ports for {}: rest={} tcp={} ws={} discv5={}",
+            self.name, self.rest_port, self.tcp_port, self.websocket_port, self.discv5_port
+        );
+        Ok(())
+    }
+
+    pub fn series(count: usize, base: WakuNodeConfig) -> Result<Vec<WakuNodeConfig>> {
+        let base_ip: Option<std::net::Ipv4Addr> = base.external_ip.parse().ok();
+        let shift = |port: u16, offset: u16| {
+            port.checked_add(offset)
+                .ok_or_else(|| WakuError::Config(format!("Port {} + {} overflows for a series of {} nodes", port, offset, count)))
+        };
+
+        (0..count)
+            .map(|i| {
+                let offset = u16::try_from(i)
+                    .ok()
+                    .and_then(|i| SERIES_PORT_STRIDE.checked_mul(i))
+                    .ok_or_else(|| WakuError::Config(format!("Series of {} nodes exceeds the port range", count)))?;
+                let mut config = base.clone();
+
+                config.name = format!("{}-{}", base.name, i + 1);
+                config.rest_port = shift(base.rest_port, offset)?;
+                config.tcp_port = shift(base.tcp_port, offset)?;
+                config.websocket_port = shift(base.websocket_port, offset)?;
+                config.discv5_port = shift(base.discv5_port, offset)?;
+                config.metrics_port = base.metrics_port.map(|port| shift(port, offset)).transpose()?;
+                config.hostname = base.hostname.as_ref().map(|h| format!("{}-{}", h, i + 1));
+                if let Some(ip) = base_ip {
+                    let host = u32::try_from(i)
+                        .ok()
+                        .and_then(|i| u32::from(ip).checked_add(i))
+                        .ok_or_else(|| WakuError::Config(format!("External IP {} + {} overflows", ip, i)))?;
+                    config.external_ip = std::net::Ipv4Addr::from(host).to_string();
+                }
+
+                Ok(config)
+            })
+            .collect()
+    }
+}
+
+fn flag_value<'a>(cmd: &'a [String], name: &str) -> Option<&'a str> {
+    let prefix = format!("--{}=", name);
+    cmd.iter().rev().find_map(|arg| arg.strip_prefix(&prefix))
+}
+
+fn create_port_bindings(config: &WakuNodeConfig) -> HashMap<String, Option<Vec<PortBinding>>> {
+    let mut bindings = HashMap::new();
+    
+    bindings.insert(
+        format!("{}/tcp", config.rest_port),
         Some(vec![PortBinding {
             host_ip: None,
             host_port: Some(config.rest_port.to_string()),
@@ -430,7 +3778,7 @@ fn create_port_bindings(config: &WakuNodeConfig) -> HashMap<String, Option<Vec<P
     );
     
     bindings.insert(
-        format!("{}/tcp", config.tcp_port),
+        format!("{}/{}", config.tcp_port, config.transport.protocol()),
         Some(vec![PortBinding {
             host_ip: None,
             host_port: Some(config.tcp_port.to_string()),
@@ -445,63 +3793,1016 @@ fn create_port_bindings(config: &WakuNodeConfig) -> HashMap<String, Option<Vec<P
         }])
     );
     
-    bindings.insert(
-        format!("{}/udp", config.discv5_port),
-        Some(vec![PortBinding {
-            host_ip: None,
-            host_port: Some(config.discv5_port.to_string()),
-        }])
-    );
+    if config.discv5 {
+        bindings.insert(
+            format!("{}/udp", config.discv5_port),
+            Some(vec![PortBinding {
+                host_ip: None,
+                host_port: Some(config.discv5_port.to_string()),
+            }])
+        );
+    }
+
+    if let Some(metrics_port) = config.metrics_port {
+        bindings.insert(
+            format!("{}/tcp", metrics_port),
+            Some(vec![PortBinding {
+                host_ip: None,
+                host_port: Some(metrics_port.to_string()),
+            }])
+        );
+    }
     
     bindings
 }
 
-fn create_exposed_ports(config: &WakuNodeConfig) -> HashMap<String, HashMap<(), ()>> {
-    let mut ports = HashMap::new();
-    ports.insert(format!("{}/tcp", config.rest_port), HashMap::new());
-    ports.insert(format!("{}/tcp", config.tcp_port), HashMap::new());
-    ports.insert(format!("{}/tcp", config.websocket_port), HashMap::new());
-    ports.insert(format!("{}/udp", config.discv5_port), HashMap::new());
-    ports
+fn create_binds(config: &WakuNodeConfig) -> Option<Vec<String>> {
+    let mut binds = Vec::new();
+
+    if let Some(dir) = &config.store_db_dir {
+        binds.push(format!("{}:{}", dir.display(), STORE_DB_MOUNT));
+    }
+
+    if let Some(wss) = &config.websocket_secure {
+        binds.push(format!("{}:{}/{}:ro", wss.cert_path.display(), WSS_CERT_MOUNT, WSS_CERT_FILE));
+        binds.push(format!("{}:{}/{}:ro", wss.key_path.display(), WSS_CERT_MOUNT, WSS_KEY_FILE));
+    }
+
+    if let Some(rln) = &config.rln {
+        binds.push(format!("{}:{}", rln.keystore_dir.display(), RLN_KEYSTORE_MOUNT));
+    }
+
+    if binds.is_empty() {
+        None
+    } else {
+        Some(binds)
+    }
+}
+
+fn create_ulimits(config: &WakuNodeConfig) -> Option<Vec<ResourcesUlimits>> {
+    if config.ulimits.is_empty() {
+        return None;
+    }
+
+    Some(config.ulimits
+        .iter()
+        .map(|(name, soft, hard)| ResourcesUlimits {
+            name: Some(name.clone()),
+            soft: Some(*soft),
+            hard: Some(*hard),
+        })
+        .collect())
+}
+
+fn create_log_config(config: &WakuNodeConfig) -> Option<HostConfigLogConfig> {
+    let mut options = HashMap::new();
+
+    if let Some(max_size) = &config.log_max_size {
+        options.insert("max-size".to_string(), max_size.clone());
+    }
+    if let Some(max_files) = config.log_max_files {
+        options.insert("max-file".to_string(), max_files.to_string());
+    }
+
+    if options.is_empty() {
+        return None;
+    }
+
+    Some(HostConfigLogConfig {
+        typ: Some("json-file".to_string()),
+        config: Some(options),
+    })
+}
+
+fn create_exposed_ports(config: &WakuNodeConfig) -> HashMap<String, HashMap<(), ()>> {
+    let mut ports = HashMap::new();
+    ports.insert(format!("{}/tcp", config.rest_port), HashMap::new());
+    ports.insert(format!("{}/{}", config.tcp_port, config.transport.protocol()), HashMap::new());
+    ports.insert(format!("{}/tcp", config.websocket_port), HashMap::new());
+    if config.discv5 {
+        ports.insert(format!("{}/udp", config.discv5_port), HashMap::new());
+    }
+    if let Some(metrics_port) = config.metrics_port {
+        ports.insert(format!("{}/tcp", metrics_port), HashMap::new());
+    }
+    ports
+}
+
+fn create_waku_command(config: &WakuNodeConfig) -> Vec<String> {
+    let mut cmd = vec![
+        "--listen-address=0.0.0.0".to_string(),
+        "--rest=true".to_string(),
+        "--rest-admin=true".to_string(),
+        "--websocket-support=true".to_string(),
+        "--log-level=INFO".to_string(), // Reduced log level for cleaner output
+        format!("--rest-relay-cache-capacity={}", config.relay_cache_capacity),
+        format!("--websocket-port={}", config.websocket_port),
+        format!("--rest-port={}", config.rest_port),
+        "--rest-address=0.0.0.0".to_string(),
+        format!("--nat=extip:{}", config.external_ip),
+        format!("--relay={}", config.relay),
+        format!("--max-connections={}", config.max_connections),
+    ];
+
+    match config.transport {
+        Transport::Tcp => cmd.push(format!("--tcp-port={}", config.tcp_port)),
+        Transport::Quic => {
+            cmd.push("--quic-support=true".to_string());
+            cmd.push(format!("--quic-port={}", config.tcp_port));
+        }
+    }
+
+    if config.peer_exchange {
+        cmd.push("--peer-exchange=true".to_string());
+    }
+
+    if config.discv5 {
+        cmd.push("--discv5-discovery=true".to_string());
+        cmd.push(format!("--discv5-udp-port={}", config.discv5_port));
+
+        for bootstrap in config.bootstrap_node.iter().chain(&config.bootstrap_nodes) {
+            cmd.push(format!("--discv5-bootstrap-node={}", bootstrap));
+            info!("Added bootstrap node: {}", bootstrap);
+        }
+    }
+
+    if let Some(metrics_port) = config.metrics_port {
+        cmd.push("--metrics-server=true".to_string());
+        cmd.push("--metrics-server-address=0.0.0.0".to_string());
+        cmd.push(format!("--metrics-server-port={}", metrics_port));
+    }
+
+    if let Some(px_node) = &config.peer_exchange_node {
+        cmd.push(format!("--peer-exchange-node={}", px_node));
+    }
+
+    for static_node in &config.static_nodes {
+        cmd.push(format!("--staticnode={}", static_node));
+        info!("Added static node: {}", static_node);
+    }
+
+    if config.store {
+        cmd.push("--store=true".to_string());
+
+        if let Some(retention) = config.store_retention_time_secs {
+            cmd.push(format!("--store-message-retention-policy=time:{}", retention));
+        }
+    }
+
+    if config.websocket_secure.is_some() {
+        cmd.push("--websocket-secure-support=true".to_string());
+        cmd.push(format!("--websocket-secure-cert-path={}/{}", WSS_CERT_MOUNT, WSS_CERT_FILE));
+        cmd.push(format!("--websocket-secure-key-path={}/{}", WSS_CERT_MOUNT, WSS_KEY_FILE));
+    }
+
+    if config.store_db_dir.is_some() {
+        cmd.push(format!("--store-message-db-url=sqlite://{}/{}", STORE_DB_MOUNT, STORE_DB_FILE));
+    }
+
+    for origin in &config.rest_allow_origins {
+        cmd.push(format!("--rest-allow-origin={}", origin));
+    }
+
+    for topic in &config.pubsub_topics {
+        cmd.push(format!("--pubsub-topic={}", topic));
+    }
+
+    if let Some(cluster_id) = config.cluster_id {
+        cmd.push(format!("--cluster-id={}", cluster_id));
+    }
+
+    for shard in &config.shards {
+        cmd.push(format!("--shard={}", shard));
+    }
+
+    for topic in &config.content_topics {
+        cmd.push(format!("--content-topic={}", topic));
+    }
+
+    if let Some(agent) = &config.agent_string {
+        cmd.push(format!("--agent-string={}", agent));
+    }
+
+    if let Some(size) = config.max_msg_size_kib {
+        cmd.push(format!("--max-msg-size={}KiB", size));
+    }
+
+    if config.keep_alive {
+        cmd.push("--keep-alive=true".to_string());
+    }
+
+    cmd.extend(config.extra_args.iter().cloned());
+    
+    cmd
+}
+
+const SHARDED_PUBSUB_PREFIX: &str = "/waku/2/rs/";
+
+pub fn pubsub_topic(cluster_id: u16, shard: u16) -> String {
+    format!("{}{}/{}", SHARDED_PUBSUB_PREFIX, cluster_id, shard)
+}
+
+pub fn parse_pubsub_topic(topic: &str) -> Result<(u16, u16)> {
+    let rest = topic.strip_prefix(SHARDED_PUBSUB_PREFIX)
+        .with_context(|| format!("Pubsub topic {} does not start with {}", topic, SHARDED_PUBSUB_PREFIX))?;
+
+    let (cluster_id, shard) = rest.split_once('/')
+        .with_context(|| format!("Pubsub topic {} is missing a shard", topic))?;
+    let cluster_id = cluster_id.parse()
+        .with_context(|| format!("Invalid cluster id in pubsub topic {}", topic))?;
+    let shard = shard.parse()
+        .with_context(|| format!("Invalid shard in pubsub topic {}", topic))?;
+
+    Ok((cluster_id, shard))
 }
 
-fn create_waku_command(config: &WakuNodeConfig) -> Vec<String> {
-    let mut cmd = vec![
-        "--listen-address=0.0.0.0".to_string(),
-        "--rest=true".to_string(),
-        "--rest-admin=true".to_string(),
-        "--websocket-support=true".to_string(),
-        "--log-level=INFO".to_string(), // Reduced log level for cleaner output
-        "--rest-relay-cache-capacity=100".to_string(),
-        format!("--websocket-port={}", config.websocket_port),
-        format!("--rest-port={}", config.rest_port),
-        format!("--tcp-port={}", config.tcp_port),
-        format!("--discv5-udp-port={}", config.discv5_port),
-        "--rest-address=0.0.0.0".to_string(),
-        format!("--nat=extip:{}", config.external_ip),
-        "--peer-exchange=true".to_string(),
-        "--discv5-discovery=true".to_string(),
-        "--relay=true".to_string(),
-        "--max-connections=50".to_string(), // Allow more connections
-    ];
-    
-    if let Some(bootstrap) = &config.bootstrap_node {
-        cmd.push(format!("--discv5-bootstrap-node={}", bootstrap));
-        info!("Added bootstrap node: {}", bootstrap);
+pub fn random_content_topic(app: &str) -> String {
+    format!("/{}/2/{}/proto", app.trim_matches('/'), uuid::Uuid::new_v4().simple())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn compress(self, data: &[u8]) -> Result<Vec<u8>> {
+        use std::io::Write;
+
+        match self {
+            Compression::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data).context("Failed to gzip payload")?;
+                encoder.finish().context("Failed to gzip payload")
+            }
+            Compression::Zstd => zstd::encode_all(data, 0).context("Failed to zstd payload"),
+        }
+    }
+
+    pub fn decompress(self, data: &[u8]) -> Result<Vec<u8>> {
+        use std::io::Read;
+
+        match self {
+            Compression::Gzip => {
+                let mut decoded = Vec::new();
+                flate2::read::GzDecoder::new(data)
+                    .read_to_end(&mut decoded)
+                    .context("Failed to gunzip payload")?;
+                Ok(decoded)
+            }
+            Compression::Zstd => zstd::decode_all(data).context("Failed to unzstd payload"),
+        }
     }
-    
-    cmd
 }
 
 pub fn create_test_message(content: &str, topic: &str) -> Message {
-    use base64::{Engine, engine::general_purpose};
+    create_test_message_with_compression(content, topic, None)
+        .expect("Uncompressed messages cannot fail to encode")
+}
+
+pub fn create_test_message_with_compression(
+    content: &str,
+    topic: &str,
+    compress: Option<Compression>,
+) -> Result<Message> {
+    let payload = match compress {
+        Some(compression) => compression.compress(content.as_bytes())?,
+        None => content.as_bytes().to_vec(),
+    };
     
+    Ok(create_binary_message(&payload, topic))
+}
+
+pub fn create_binary_message(payload: &[u8], topic: &str) -> Message {
+    use base64::{Engine, engine::general_purpose};
+
     Message {
-        payload: general_purpose::STANDARD.encode(content),
+        payload: general_purpose::STANDARD.encode(payload),
         content_topic: topic.to_string(),
         timestamp: std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
-            .as_secs(),
+            .as_nanos() as u64,
+        ephemeral: None,
+    }
+}
+
+pub fn diff_messages(a: &[ReceivedMessage], b: &[ReceivedMessage]) -> MessageDiff {
+    use std::collections::HashSet;
+
+    // Hashes are only comparable when every message carries one
+    let by_hash = a.iter().chain(b.iter()).all(|m| m.hash.is_some());
+    let key = |m: &ReceivedMessage| -> Vec<u8> {
+        match &m.hash {
+            Some(hash) if by_hash => hash.as_bytes().to_vec(),
+            _ => m.decoded_payload().unwrap_or_else(|_| m.payload.as_bytes().to_vec()),
+        }
+    };
+
+    let keys_a: HashSet<Vec<u8>> = a.iter().map(key).collect();
+    let keys_b: HashSet<Vec<u8>> = b.iter().map(key).collect();
+
+    let mut diff = MessageDiff::default();
+    for message in a {
+        if keys_b.contains(&key(message)) {
+            diff.common.push(message.clone());
+        } else {
+            diff.only_in_a.push(message.clone());
+        }
+    }
+    diff.only_in_b = b.iter().filter(|m| !keys_a.contains(&key(m))).cloned().collect();
+
+    diff
+}
+
+pub fn parse_metrics(text: &str) -> HashMap<String, f64> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            // Label values may contain spaces, so split after the closing brace when present
+            let split_at = match line.rfind('}') {
+                Some(brace) => brace + 1,
+                None => line.find(char::is_whitespace)?,
+            };
+            let (series, rest) = line.split_at(split_at);
+            let value = rest.split_whitespace().next()?.parse::<f64>().ok()?;
+            Some((series.trim().to_string(), value))
+        })
+        .collect()
+}
+
+pub fn metric_name(series: &str) -> &str {
+    series.split('{').next().unwrap_or(series)
+}
+
+pub fn metric_label<'a>(series: &'a str, label: &str) -> Option<&'a str> {
+    let labels = series.split_once('{')?.1.strip_suffix('}')?;
+    let needle = format!("{}=\"", label);
+    let start = labels.find(&needle)? + needle.len();
+    let end = labels[start..].find('"')?;
+    Some(&labels[start..start + end])
+}
+
+pub fn parse_version(version: &str) -> Result<(u64, u64, u64)> {
+    // Handles "v0.24.0", "0.28.0" and git-describe forms like "v0.24.0-rc.0-12-gabc123"
+    let core = version
+        .trim()
+        .trim_start_matches('v')
+        .split(['-', '+'])
+        .next()
+        .unwrap_or_default();
+
+    let mut parts = core.split('.').map(|part| part.parse::<u64>());
+    let mut next = || -> Result<u64> {
+        parts.next()
+            .unwrap_or(Ok(0))
+            .with_context(|| format!("Invalid version string: {}", version))
+    };
+
+    Ok((next()?, next()?, next()?))
+}
+
+// Last status line printed by wget -S, e.g. "  HTTP/1.1 200 OK" or "wget: server returned error: HTTP/1.1 404 Not Found"
+fn wget_status(stderr: &str) -> Option<u16> {
+    stderr
+        .lines()
+        .rev()
+        .find_map(|line| line[line.find("HTTP/")?..].split_whitespace().nth(1)?.parse().ok())
+}
+
+// Headers of the last response in curl -D or wget -S output; earlier blocks belong to redirects
+fn dumped_headers(dump: &str) -> HeaderMap {
+    let lines: Vec<&str> = dump.lines().map(str::trim).collect();
+    let start = lines.iter().rposition(|line| line.starts_with("HTTP/")).map_or(0, |i| i + 1);
+
+    lines[start..]
+        .iter()
+        .filter_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            let name = reqwest::header::HeaderName::from_bytes(name.trim().as_bytes()).ok()?;
+            let value = reqwest::header::HeaderValue::from_str(value.trim()).ok()?;
+            Some((name, value))
+        })
+        .collect()
+}
+
+fn parse_http_date(date: &str) -> Result<SystemTime> {
+    // IMF-fixdate, e.g. "Sun, 06 Nov 1994 08:49:37 GMT"
+    let parts: Vec<&str> = date.split_whitespace().collect();
+    if parts.len() != 6 || parts[5] != "GMT" {
+        return Err(WakuError::ParseResponse(format!("Unsupported HTTP date format: {}", date)));
+    }
+
+    let day: i64 = parts[1].parse().context("Invalid day in HTTP date")?;
+    let month = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"]
+        .iter()
+        .position(|m| *m == parts[2])
+        .with_context(|| format!("Invalid month in HTTP date: {}", parts[2]))? as i64 + 1;
+    let year: i64 = parts[3].parse().context("Invalid year in HTTP date")?;
+
+    let time: Vec<i64> = parts[4]
+        .split(':')
+        .map(|t| t.parse::<i64>())
+        .collect::<std::result::Result<_, _>>()
+        .context("Invalid time in HTTP date")?;
+    if time.len() != 3 {
+        return Err(WakuError::ParseResponse(format!("Invalid time in HTTP date: {}", parts[4])));
+    }
+
+    // Days since the Unix epoch for a proleptic Gregorian date (Howard Hinnant's algorithm)
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    let secs = days * 86400 + time[0] * 3600 + time[1] * 60 + time[2];
+    let secs = u64::try_from(secs).context("HTTP date is before the Unix epoch")?;
+
+    Ok(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+pub fn decode_enr_ip(enr: &str) -> Result<Option<std::net::Ipv4Addr>> {
+    use base64::{Engine, engine::general_purpose};
+
+    let encoded = enr.strip_prefix("enr:")
+        .context("ENR must start with 'enr:'")?;
+    let raw = general_purpose::URL_SAFE_NO_PAD
+        .decode(encoded.trim_end_matches('='))
+        .context("Failed to decode ENR base64")?;
+
+    let (is_list, mut fields, _) = rlp_split(&raw)?;
+    if !is_list {
+        return Err(WakuError::ParseResponse("ENR is not an RLP list".to_string()));
+    }
+
+    let mut items = Vec::new();
+    while !fields.is_empty() {
+        let (_, item, rest) = rlp_split(fields)?;
+        items.push(item);
+        fields = rest;
+    }
+
+    // Layout is [signature, seq, key1, value1, key2, value2, ...]
+    let ip = items
+        .get(2..)
+        .unwrap_or_default()
+        .chunks_exact(2)
+        .find(|pair| pair[0] == &b"ip"[..] && pair[1].len() == 4)
+        .map(|pair| std::net::Ipv4Addr::new(pair[1][0], pair[1][1], pair[1][2], pair[1][3]));
+
+    Ok(ip)
+}
+
+fn rlp_split(data: &[u8]) -> Result<(bool, &[u8], &[u8])> {
+    let (&prefix, rest) = data.split_first()
+        .context("Unexpected end of RLP data")?;
+
+    let (is_list, length_of_length, short_length) = match prefix {
+        0x00..=0x7f => return Ok((false, &data[..1], rest)),
+        0x80..=0xb7 => (false, 0, (prefix - 0x80) as usize),
+        0xb8..=0xbf => (false, (prefix - 0xb7) as usize, 0),
+        0xc0..=0xf7 => (true, 0, (prefix - 0xc0) as usize),
+        0xf8..=0xff => (true, (prefix - 0xf7) as usize, 0),
+    };
+
+    if rest.len() < length_of_length {
+        return Err(WakuError::ParseResponse("Truncated RLP length prefix".to_string()));
+    }
+    let (length_bytes, rest) = rest.split_at(length_of_length);
+    let length = if length_of_length > 0 {
+        length_bytes.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize)
+    } else {
+        short_length
+    };
+
+    if rest.len() < length {
+        return Err(WakuError::ParseResponse("Truncated RLP item".to_string()));
+    }
+    let (payload, remainder) = rest.split_at(length);
+
+    Ok((is_list, payload, remainder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Example record from EIP-778
+    const EXAMPLE_ENR: &str = "enr:-IS4QHCYrYZbAKWCBRlAy5zzaDZXJBGkcnh4MHcBFZntXNFrdvJjX04jRzjzCBOonrkTfj499SZuOh8R33Ls8RRcy5wBgmlkgnY0gmlwhH8AAAGJc2VjcDI1NmsxoQPKY0yuDUmstAHYpMa2_oxVtw0RW_QAdpzBQA8yWM0xOIN1ZHCCdl8";
+
+    fn sequenced(seq: u64) -> ReceivedMessage {
+        let message = create_test_message(&format!("{}{}", SEQUENCE_PREFIX, seq), "/test/1/gaps/proto");
+        ReceivedMessage {
+            payload: message.payload,
+            content_topic: message.content_topic,
+            timestamp: message.timestamp,
+            hash: None,
+        }
+    }
+
+    #[test]
+    fn decode_enr_ip_reads_example_record() {
+        let ip = decode_enr_ip(EXAMPLE_ENR).unwrap();
+        assert_eq!(ip, Some(std::net::Ipv4Addr::new(127, 0, 0, 1)));
+    }
+
+    #[test]
+    fn decode_enr_ip_rejects_malformed_records() {
+        assert!(decode_enr_ip(EXAMPLE_ENR.trim_start_matches("enr:")).is_err());
+        assert!(decode_enr_ip(&EXAMPLE_ENR[..40]).is_err());
+        // A bare RLP string instead of a list
+        assert!(decode_enr_ip("enr:gmlw").is_err());
+    }
+
+    #[test]
+    fn rlp_split_handles_each_prefix_range() {
+        assert_eq!(rlp_split(&[0x05, 0xaa]).unwrap(), (false, &[0x05][..], &[0xaa][..]));
+        assert_eq!(rlp_split(&[0x82, 0x69, 0x70]).unwrap(), (false, &b"ip"[..], &[][..]));
+        assert_eq!(rlp_split(&[0xc2, 0x01, 0x02, 0x03]).unwrap(), (true, &[0x01, 0x02][..], &[0x03][..]));
+
+        let mut long = vec![0xb8, 56];
+        long.extend([0x11; 56]);
+        let (is_list, payload, rest) = rlp_split(&long).unwrap();
+        assert!(!is_list);
+        assert_eq!(payload.len(), 56);
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn rlp_split_rejects_truncated_input() {
+        assert!(rlp_split(&[]).is_err());
+        assert!(rlp_split(&[0x83, 0x01]).is_err());
+        assert!(rlp_split(&[0xb9, 0x01]).is_err());
+    }
+
+    #[test]
+    fn wget_status_reads_last_status_line() {
+        let redirected = "  HTTP/1.1 302 Found\n  Location: /health\n  HTTP/1.1 200 OK\n  Content-Length: 2\n";
+        assert_eq!(wget_status(redirected), Some(200));
+        assert_eq!(wget_status("  HTTP/1.1 404 Not Found\nwget: server returned error: HTTP/1.1 404 Not Found\n"), Some(404));
+        assert_eq!(wget_status("wget: can't connect to remote host (127.0.0.1): Connection refused\n"), None);
+    }
+
+    #[test]
+    fn dumped_headers_reads_final_response_only() {
+        let curl = "HTTP/1.1 301 Moved\r\nLocation: /info\r\n\r\nHTTP/1.1 200 OK\r\nDate: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n";
+        let headers = dumped_headers(curl);
+        assert_eq!(headers.get(reqwest::header::DATE).unwrap(), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert!(headers.get(reqwest::header::LOCATION).is_none());
+
+        let wget = "  HTTP/1.1 200 OK\n  Content-Type: application/json\n  Date: Sun, 06 Nov 1994 08:49:37 GMT\n";
+        assert_eq!(dumped_headers(wget).len(), 2);
+    }
+
+    #[test]
+    fn parse_http_date_reads_imf_fixdate() {
+        let time = parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+        assert_eq!(time, UNIX_EPOCH + Duration::from_secs(784111777));
+
+        let leap_day = parse_http_date("Thu, 29 Feb 2024 00:00:00 GMT").unwrap();
+        assert_eq!(leap_day, UNIX_EPOCH + Duration::from_secs(1709164800));
+    }
+
+    #[test]
+    fn parse_http_date_rejects_other_formats() {
+        assert!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT").is_err());
+        assert!(parse_http_date("Sun, 06 Nov 1994 08:49:37 PST").is_err());
+        assert!(parse_http_date("Sun, 06 Foo 1994 08:49:37 GMT").is_err());
+        assert!(parse_http_date("Sun, 06 Nov 1994 08:49 GMT").is_err());
+    }
+
+    #[test]
+    fn parse_version_handles_release_and_git_describe_forms() {
+        assert_eq!(parse_version("v0.24.0").unwrap(), (0, 24, 0));
+        assert_eq!(parse_version("0.28.1").unwrap(), (0, 28, 1));
+        assert_eq!(parse_version("v0.24.0-rc.0-12-gabc123").unwrap(), (0, 24, 0));
+        assert_eq!(parse_version("v0.30.0+build.7").unwrap(), (0, 30, 0));
+        assert_eq!(parse_version("v1.2").unwrap(), (1, 2, 0));
+        assert!(parse_version("master").is_err());
+    }
+
+    #[test]
+    fn parse_pubsub_topic_round_trips_static_shards() {
+        assert_eq!(parse_pubsub_topic(&pubsub_topic(1, 3)).unwrap(), (1, 3));
+        assert_eq!(parse_pubsub_topic("/waku/2/rs/16/128").unwrap(), (16, 128));
+    }
+
+    #[test]
+    fn parse_pubsub_topic_rejects_malformed_topics() {
+        assert!(parse_pubsub_topic("/waku/2/default-waku/proto").is_err());
+        assert!(parse_pubsub_topic("/waku/2/rs/1").is_err());
+        assert!(parse_pubsub_topic("/waku/2/rs/x/1").is_err());
+        assert!(parse_pubsub_topic("/waku/2/rs/1/70000").is_err());
+    }
+
+    #[test]
+    fn detect_gaps_reports_missing_and_trailing_messages() {
+        let received = [sequenced(0), sequenced(2)];
+        assert_eq!(detect_gaps(&received, 4), vec![1, 3]);
+        assert_eq!(detect_gaps(&[], 3), vec![0, 1, 2]);
+        assert!(detect_gaps(&received[..1], 1).is_empty());
+    }
+
+    fn received(payload: &str, hash: Option<&str>) -> ReceivedMessage {
+        let message = create_test_message(payload, "/test/1/diff/proto");
+        ReceivedMessage {
+            payload: message.payload,
+            content_topic: message.content_topic,
+            timestamp: message.timestamp,
+            hash: hash.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn diff_messages_matches_by_hash_when_all_have_one() {
+        let a = [received("one", Some("0x01")), received("two", Some("0x02"))];
+        let b = [received("other", Some("0x02")), received("three", Some("0x03"))];
+
+        let diff = diff_messages(&a, &b);
+        assert_eq!(diff.common.len(), 1);
+        assert_eq!(diff.only_in_a[0].hash.as_deref(), Some("0x01"));
+        assert_eq!(diff.only_in_b[0].hash.as_deref(), Some("0x03"));
+        assert!(!diff.is_identical());
+    }
+
+    #[test]
+    fn diff_messages_falls_back_to_payload_when_a_hash_is_missing() {
+        let a = [received("one", Some("0x01")), received("two", None)];
+        let b = [received("two", Some("0x02")), received("one", None)];
+
+        let diff = diff_messages(&a, &b);
+        assert_eq!(diff.common.len(), 2);
+        assert!(diff.is_identical());
+    }
+
+
+    #[test]
+    fn parse_metrics_skips_comments_and_keeps_labelled_series() {
+        let text = "# HELP waku_peers Number of peers\n\
+                    # TYPE waku_peers gauge\n\
+                    waku_peers 3\n\
+                    \n\
+                    discovery_routing_table_size{kind=\"dis v5\"} 12.5 1700000000\n\
+                    broken_line not_a_number\n";
+
+        let metrics = parse_metrics(text);
+        assert_eq!(metrics.len(), 2);
+        assert_eq!(metrics["waku_peers"], 3.0);
+        assert_eq!(metrics["discovery_routing_table_size{kind=\"dis v5\"}"], 12.5);
+    }
+
+
+    #[test]
+    fn metric_label_reads_named_label() {
+        let series = "libp2p_gossipsub_peer_score{peer=\"16Uiu2HAm\",topic=\"/waku/2/rs/0/0\"}";
+        assert_eq!(metric_name(series), "libp2p_gossipsub_peer_score");
+        assert_eq!(metric_label(series, "peer"), Some("16Uiu2HAm"));
+        assert_eq!(metric_label(series, "topic"), Some("/waku/2/rs/0/0"));
+        assert_eq!(metric_label(series, "shard"), None);
+        assert_eq!(metric_label("waku_peers", "peer"), None);
+    }
+
+
+    #[test]
+    fn parse_health_reads_json_and_plain_text() {
+        let body = r#"{"nodeHealth":"Ready","protocolsHealth":[{"Relay":"Ready"},{"Store":"NOT_MOUNTED"}]}"#;
+        let report = parse_health(StatusCode::OK, body);
+        assert!(report.healthy);
+        assert_eq!(report.protocols["Relay"], "Ready");
+        assert_eq!(report.protocols["Store"], "NOT_MOUNTED");
+
+        let initializing = parse_health(StatusCode::OK, r#"{"nodeHealth":"Initializing","protocolsHealth":{"Relay":"Ready"}}"#);
+        assert!(!initializing.healthy);
+        assert_eq!(initializing.protocols.len(), 1);
+
+        assert!(parse_health(StatusCode::OK, "Node is healthy\n").healthy);
+        assert!(!parse_health(StatusCode::SERVICE_UNAVAILABLE, "Node is not ready").healthy);
+    }
+
+
+    #[test]
+    fn publish_response_extracts_message_hash() {
+        let json = PublishResponse::from_body(200, r#"{"messageHash":"0xabc"}"#.to_string());
+        assert_eq!(json.message_hash.as_deref(), Some("0xabc"));
+
+        let legacy = PublishResponse::from_body(200, r#"{"hash":"0xdef"}"#.to_string());
+        assert_eq!(legacy.message_hash.as_deref(), Some("0xdef"));
+
+        let plain = PublishResponse::from_body(200, " 0x123\n".to_string());
+        assert_eq!(plain.message_hash.as_deref(), Some("0x123"));
+
+        assert!(PublishResponse::from_body(200, "OK".to_string()).message_hash.is_none());
+    }
+
+
+    #[test]
+    fn parse_store_messages_handles_v3_and_bare_entries() {
+        let body = json!({
+            "messages": [
+                {
+                    "messageHash": "0x01",
+                    "pubsubTopic": "/waku/2/rs/0/0",
+                    "message": {"payload": "aGk=", "contentTopic": "/test/1/store/proto", "timestamp": 1}
+                },
+                {"payload": "aGk=", "contentTopic": "/test/1/store/proto", "timestamp": 2}
+            ],
+            "paginationCursor": "0x01"
+        });
+
+        let messages = parse_store_messages(&body).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].hash.as_deref(), Some("0x01"));
+        assert!(messages[1].hash.is_none());
+        assert_eq!(parse_store_cursor(&body).as_deref(), Some("0x01"));
+
+        assert!(parse_store_messages(&json!({})).unwrap().is_empty());
+        assert!(parse_store_messages(&json!({"messages": [{"payload": 1}]})).is_err());
+    }
+
+    #[test]
+    fn parse_store_cursor_ignores_empty_cursors() {
+        assert_eq!(parse_store_cursor(&json!({"paginationCursor": null})), None);
+        assert_eq!(parse_store_cursor(&json!({"cursor": ""})), None);
+        assert_eq!(parse_store_cursor(&json!({"cursor": {"digest": "ab"}})).as_deref(), Some(r#"{"digest":"ab"}"#));
+    }
+
+
+    #[test]
+    fn store_query_params_only_include_set_fields() {
+        assert!(StoreQuery::default().params().is_empty());
+
+        let query = StoreQuery {
+            pubsub_topic: Some("/waku/2/rs/0/0".to_string()),
+            end_time: Some(20),
+            cursor: Some("0xff".to_string()),
+            ..Default::default()
+        }
+        .content_topic("/a/1/x/proto")
+        .content_topic("/b/1/y/proto")
+        .hash("0x01");
+
+        assert_eq!(
+            query.params(),
+            vec![
+                ("contentTopics", "/a/1/x/proto,/b/1/y/proto".to_string()),
+                ("pubsubTopic", "/waku/2/rs/0/0".to_string()),
+                ("endTime", "20".to_string()),
+                ("hashes", "0x01".to_string()),
+                ("cursor", "0xff".to_string()),
+            ]
+        );
+        assert_eq!(
+            encode_query(&query.params()[..2]),
+            "contentTopics=%2Fa%2F1%2Fx%2Fproto%2C%2Fb%2F1%2Fy%2Fproto&pubsubTopic=%2Fwaku%2F2%2Frs%2F0%2F0"
+        );
+    }
+
+
+    #[test]
+    fn compression_round_trips_through_message_payload() {
+        let content = "compressible ".repeat(64);
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let message = create_test_message_with_compression(&content, "/test/1/zip/proto", Some(compression)).unwrap();
+            let received = ReceivedMessage {
+                payload: message.payload,
+                content_topic: message.content_topic,
+                timestamp: message.timestamp,
+                hash: None,
+            };
+
+            assert!(received.decoded_payload().unwrap().len() < content.len());
+            assert_eq!(received.decompressed_payload(compression).unwrap(), content.as_bytes());
+        }
+
+        assert!(Compression::Gzip.decompress(b"not gzip").is_err());
+        assert!(Compression::Zstd.decompress(b"not zstd").is_err());
+    }
+
+
+    #[test]
+    fn retry_policy_delay_doubles_up_to_the_cap() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay_for(1), Duration::from_millis(500));
+        assert_eq!(policy.delay_for(2), Duration::from_secs(1));
+        assert_eq!(policy.delay_for(3), Duration::from_secs(2));
+        assert_eq!(policy.delay_for(5), Duration::from_secs(5));
+        assert_eq!(policy.delay_for(u32::MAX), Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn retry_policy_stops_after_max_attempts() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            initial_delay: Duration::from_millis(1),
+            ..Default::default()
+        };
+
+        let mut attempts = 0;
+        let result: Result<()> = policy
+            .run(|| {
+                attempts += 1;
+                async { Err(WakuError::Failed("still down".to_string())) }
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(attempts, 3);
+
+        let mut attempts = 0;
+        let value = policy
+            .run(|| {
+                attempts += 1;
+                let ready = attempts == 2;
+                async move { if ready { Ok(attempts) } else { Err(WakuError::Failed("not yet".to_string())) } }
+            })
+            .await
+            .unwrap();
+        assert_eq!(value, 2);
+    }
+
+
+    #[test]
+    fn subnet_helpers_stay_inside_the_cidr() {
+        // Docker is only contacted lazily, so this runs without a daemon
+        let framework = WakuTestFramework::with_options(FrameworkOptions {
+            subnet: "10.7.3.0/24".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(framework.subnet_ip(100).unwrap(), "10.7.3.100");
+        assert!(framework.subnet_ip(256).is_err());
+        assert!(framework.subnet_contains("10.7.3.254").unwrap());
+        assert!(!framework.subnet_contains("10.7.4.1").unwrap());
+        assert!(!framework.subnet_contains("not-an-ip").unwrap());
+
+        let isolated = WakuTestFramework::isolated().unwrap();
+        let gateway = &isolated.options().gateway;
+        assert!(isolated.subnet_contains(gateway).unwrap());
+        assert!(!isolated.subnet_contains(&FrameworkOptions::default().gateway).unwrap());
+    }
+
+
+    #[test]
+    fn create_waku_command_emits_static_nodes() {
+        let config = WakuNodeConfig {
+            static_nodes: vec!["/ip4/10.0.0.2/tcp/60000/p2p/16Uiu2HAmA".to_string(), "/ip4/10.0.0.3/tcp/60000/p2p/16Uiu2HAmB".to_string()],
+            ..Default::default()
+        };
+
+        let cmd = create_waku_command(&config);
+        let static_nodes: Vec<_> = cmd.iter().filter(|arg| arg.starts_with("--staticnode=")).collect();
+        assert_eq!(static_nodes, ["--staticnode=/ip4/10.0.0.2/tcp/60000/p2p/16Uiu2HAmA", "--staticnode=/ip4/10.0.0.3/tcp/60000/p2p/16Uiu2HAmB"]);
+        assert!(!create_waku_command(&WakuNodeConfig::default()).iter().any(|arg| arg.starts_with("--staticnode=")));
+    }
+
+
+    #[test]
+    fn create_waku_command_emits_each_pubsub_topic() {
+        let config = WakuNodeConfig {
+            pubsub_topics: vec![pubsub_topic(1, 0), pubsub_topic(1, 1)],
+            ..Default::default()
+        };
+
+        let cmd = create_waku_command(&config);
+        assert!(cmd.contains(&"--pubsub-topic=/waku/2/rs/1/0".to_string()));
+        assert!(cmd.contains(&"--pubsub-topic=/waku/2/rs/1/1".to_string()));
+    }
+
+
+    #[test]
+    fn create_waku_command_emits_relay_and_store_flags() {
+        let archive = WakuNodeConfig {
+            relay: false,
+            store: true,
+            store_retention_time_secs: Some(3600),
+            ..Default::default()
+        };
+
+        let cmd = create_waku_command(&archive);
+        assert!(cmd.contains(&"--relay=false".to_string()));
+        assert!(cmd.contains(&"--store=true".to_string()));
+        assert!(cmd.contains(&"--store-message-retention-policy=time:3600".to_string()));
+
+        let relay = create_waku_command(&WakuNodeConfig::default());
+        assert!(relay.contains(&"--relay=true".to_string()));
+        assert!(!relay.iter().any(|arg| arg.starts_with("--store")));
+    }
+
+
+    #[test]
+    fn create_waku_command_omits_discovery_flags_when_disabled() {
+        let config = WakuNodeConfig {
+            discv5: false,
+            peer_exchange: false,
+            bootstrap_node: Some(EXAMPLE_ENR.to_string()),
+            ..Default::default()
+        };
+
+        let cmd = create_waku_command(&config);
+        assert!(!cmd.iter().any(|arg| arg.starts_with("--discv5")));
+        assert!(!cmd.iter().any(|arg| arg.starts_with("--peer-exchange")));
+
+        let enabled = create_waku_command(&WakuNodeConfig::default());
+        assert!(enabled.contains(&"--discv5-discovery=true".to_string()));
+        assert!(enabled.contains(&"--discv5-udp-port=22164".to_string()));
+        assert!(enabled.contains(&"--peer-exchange=true".to_string()));
+    }
+
+
+    #[test]
+    fn create_waku_command_selects_transport_port_flag() {
+        let quic = create_waku_command(&WakuNodeConfig {
+            transport: Transport::Quic,
+            ..Default::default()
+        });
+        assert!(quic.contains(&"--quic-support=true".to_string()));
+        assert!(quic.contains(&"--quic-port=22162".to_string()));
+        assert!(!quic.iter().any(|arg| arg.starts_with("--tcp-port")));
+
+        let tcp = create_waku_command(&WakuNodeConfig::default());
+        assert!(tcp.contains(&"--tcp-port=22162".to_string()));
+        assert!(!tcp.iter().any(|arg| arg.starts_with("--quic")));
+    }
+
+
+    #[test]
+    fn create_waku_command_emits_content_topic_allowlist() {
+        let config = WakuNodeConfig {
+            content_topics: vec!["/app/1/chat/proto".to_string(), "/app/1/status/proto".to_string()],
+            ..Default::default()
+        };
+
+        let cmd = create_waku_command(&config);
+        let topics: Vec<_> = cmd.iter().filter(|arg| arg.starts_with("--content-topic=")).collect();
+        assert_eq!(topics, ["--content-topic=/app/1/chat/proto", "--content-topic=/app/1/status/proto"]);
+    }
+
+
+    #[test]
+    fn create_waku_command_emits_every_bootstrap_node() {
+        let config = WakuNodeConfig {
+            bootstrap_node: Some("enr:-first".to_string()),
+            bootstrap_nodes: vec!["enr:-second".to_string(), "enr:-third".to_string()],
+            ..Default::default()
+        };
+
+        let cmd = create_waku_command(&config);
+        let bootstrap: Vec<_> = cmd.iter().filter(|arg| arg.starts_with("--discv5-bootstrap-node=")).collect();
+        assert_eq!(
+            bootstrap,
+            [
+                "--discv5-bootstrap-node=enr:-first",
+                "--discv5-bootstrap-node=enr:-second",
+                "--discv5-bootstrap-node=enr:-third",
+            ]
+        );
+    }
+
+
+    #[test]
+    fn create_waku_command_emits_cluster_and_shards() {
+        let config = WakuNodeConfig {
+            cluster_id: Some(16),
+            shards: vec![32, 64],
+            ..Default::default()
+        };
+
+        let cmd = create_waku_command(&config);
+        assert!(cmd.contains(&"--cluster-id=16".to_string()));
+        let shards: Vec<_> = cmd.iter().filter(|arg| arg.starts_with("--shard=")).collect();
+        assert_eq!(shards, ["--shard=32", "--shard=64"]);
+
+        let default = create_waku_command(&WakuNodeConfig::default());
+        assert!(!default.iter().any(|arg| arg.starts_with("--cluster-id") || arg.starts_with("--shard")));
+    }
+
+
+    #[test]
+    fn series_offsets_ports_and_ips() {
+        let configs = WakuNodeConfig::series(3, WakuNodeConfig::default()).unwrap();
+        assert_eq!(configs[2].name, "waku-node-3");
+        assert_eq!(configs[2].rest_port, 22181);
+        assert_eq!(configs[2].external_ip, "172.18.111.228");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn series_rejects_port_and_ip_overflow() {
+        let high_ports = WakuNodeConfig {
+            discv5_port: 65530,
+            ..Default::default()
+        };
+        assert!(WakuNodeConfig::series(1, high_ports.clone()).is_ok());
+        assert!(matches!(WakuNodeConfig::series(2, high_ports), Err(WakuError::Config(_))));
+
+        let last_ip = WakuNodeConfig {
+            external_ip: "255.255.255.255".to_string(),
+            ..Default::default()
+        };
+        assert!(matches!(WakuNodeConfig::series(2, last_ip), Err(WakuError::Config(_))));
+    }
+}
@@ -1,4 +1,4 @@
-use waku_test_automation::{WakuTestFramework, WakuNodeConfig, create_test_message};
+use waku_test_automation::{WakuTestFramework, WakuNodeConfig};
 use anyhow::Result;
 use tracing::{info, error};
 
@@ -10,31 +10,19 @@ async fn main() -> Result<()> {
     
     let framework = WakuTestFramework::new()?;
     
-    match run_basic_test(&framework).await {
-        Ok(_) => info!("Basic test completed successfully"),
-        Err(e) => error!("Basic test failed: {}", e),
+    let report = framework.run_smoke_test(WakuNodeConfig::default()).await?;
+    for step in &report.steps {
+        match &step.error {
+            None => info!("{} succeeded in {:?}", step.name, step.duration),
+            Some(e) => error!("{} failed after {:?}: {}", step.name, step.duration, e),
+        }
     }
-    
-    Ok(())
-}
 
-async fn run_basic_test(framework: &WakuTestFramework) -> Result<()> {
-    let config = WakuNodeConfig::default();
-    let node = framework.start_waku_node(config).await?;
-    
-    // Get node info
-    let node_info = framework.get_node_info(&node).await?;
-    info!("Node ENR: {}", node_info.enr_uri);
-    
-    // Subscribe and publish
-    let topic = "/my-app/2/chatroom-1/proto";
-    framework.subscribe_to_topic(&node, topic).await?;
-    
-    let message = create_test_message("Test message", topic);
-    framework.publish_message(&node, &message).await?;
-    
-    // Cleanup
-    framework.cleanup_node(&node).await?;
+    if report.passed() {
+        info!("Basic test completed successfully");
+    } else {
+        error!("Basic test failed");
+    }
     
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,5 +1,5 @@
 // tests/integration_tests.rs
-use waku_test_automation::{WakuTestFramework, WakuNodeConfig, create_test_message};
+use waku_test_automation::{WakuTestFramework, WakuNodeConfig, RestAccess, create_test_message};
 use std::time::Duration;
 
 const TEST_TOPIC: &str = "/my-app/2/chatroom-1/proto";
@@ -10,14 +10,10 @@ async fn test_suite_1_basic_node_operation() {
     // Initialize tracing (ignore if already initialized)
     let _ = tracing_subscriber::fmt::try_init();
     
-    let framework = WakuTestFramework::new()
+    // Isolated framework: unique name prefix, subnet and ports so suites can run in parallel
+    let framework = WakuTestFramework::isolated()
         .expect("Failed to create test framework");
 
-    // Clean up any existing containers
-    framework.cleanup_existing_containers()
-        .await
-        .expect("Failed to cleanup existing containers");
-
     let config = WakuNodeConfig::default();
     
     // Start the node
@@ -76,30 +72,22 @@ async fn test_suite_2_inter_node_communication() {
     // Initialize tracing (ignore if already initialized)
     let _ = tracing_subscriber::fmt::try_init();
     
-    let framework = WakuTestFramework::new()
+    // Isolated framework: unique name prefix, subnet and ports so suites can run in parallel
+    let framework = WakuTestFramework::isolated()
         .expect("Failed to create test framework");
 
-    // Clean up any existing containers and network
-    framework.cleanup_existing_containers()
-        .await
-        .expect("Failed to cleanup existing containers");
-    
-    let _ = framework.cleanup_network().await; // Ignore errors if network doesn't exist
-
     // Setup network
     framework.setup_network()
         .await
         .expect("Failed to setup network");
 
     // Start first node (bootstrap node)
+    // Ports are assigned dynamically by the isolated framework
     let config1 = WakuNodeConfig {
         name: "waku-node-1".to_string(),
-        rest_port: 23161,
-        tcp_port: 23162,
-        websocket_port: 23163,
-        discv5_port: 23164,
-        external_ip: "172.18.111.226".to_string(),
+        external_ip: framework.subnet_ip(10).expect("Failed to allocate node1 IP"),
         bootstrap_node: None,
+        ..Default::default()
     };
 
     let mut node1 = framework.start_waku_node(config1)
@@ -112,12 +100,12 @@ async fn test_suite_2_inter_node_communication() {
         .expect("Failed to connect node1 to network");
 
     // Get ENR URI from node1 AFTER connecting to network
-    let node1_info = framework.get_node_info(&node1)
+    framework.refresh_enr(&mut node1)
         .await
-        .expect("Failed to get node1 info");
-    node1.enr_uri = Some(node1_info.enr_uri.clone());
+        .expect("Failed to refresh node1 ENR");
+    let node1_enr = node1.enr_uri.clone().expect("Node1 ENR should be set");
 
-    println!("Node1 ENR: {}", node1_info.enr_uri);
+    println!("Node1 ENR: {}", node1_enr);
 
     // Subscribe node1 to topic
     framework.subscribe_to_topic(&node1, TEST_TOPIC)
@@ -130,12 +118,9 @@ async fn test_suite_2_inter_node_communication() {
     // Start second node with bootstrap
     let config2 = WakuNodeConfig {
         name: "waku-node-2".to_string(),
-        rest_port: 23171,
-        tcp_port: 23172,
-        websocket_port: 23173,
-        discv5_port: 23174,
-        external_ip: "172.18.111.227".to_string(),
-        bootstrap_node: Some(node1_info.enr_uri),
+        external_ip: framework.subnet_ip(11).expect("Failed to allocate node2 IP"),
+        bootstrap_node: Some(node1_enr),
+        ..Default::default()
     };
 
     let node2 = framework.start_waku_node(config2)
@@ -172,58 +157,13 @@ async fn test_suite_2_inter_node_communication() {
         println!("✅ Nodes successfully discovered each other!");
     }
 
-    // Publish message from node1
-    let message = create_test_message("Inter-node communication works!", TEST_TOPIC);
-    framework.publish_message(&node1, &message)
+    // Verify relay in both directions
+    let relay = framework.verify_bidirectional_relay(&node1, &node2, TEST_TOPIC, Duration::from_secs(10))
         .await
-        .expect("Failed to publish message from node1");
-
-    // Wait for message propagation with longer timeout
-    tokio::time::sleep(Duration::from_secs(10)).await;
+        .expect("Failed to verify relay between nodes");
 
-    // Verify node2 received the message
-    let received_messages = framework.get_messages(&node2, TEST_TOPIC)
-        .await
-        .expect("Failed to get messages from node2");
-
-    if received_messages.is_empty() {
-        // Try publishing from node2 to node1 as well
-        println!("No messages received on node2, trying reverse direction...");
-        let reverse_message = create_test_message("Reverse communication test!", TEST_TOPIC);
-        framework.publish_message(&node2, &reverse_message)
-            .await
-            .expect("Failed to publish message from node2");
-        
-        tokio::time::sleep(Duration::from_secs(5)).await;
-        
-        let node1_messages = framework.get_messages(&node1, TEST_TOPIC)
-            .await
-            .expect("Failed to get messages from node1");
-        
-        if !node1_messages.is_empty() {
-            println!("✅ Reverse communication works - nodes are connected!");
-            
-            framework.cleanup_node(&node1).await.expect("Failed to cleanup node1");
-            framework.cleanup_node(&node2).await.expect("Failed to cleanup node2");
-            framework.cleanup_network().await.expect("Failed to cleanup network");
-            
-            println!("✅ Test Suite 2: Inter-Node Communication - PASSED");
-            return;
-        }
-    }
-
-    assert!(!received_messages.is_empty(), "Node2 should have received messages");
-    
-    let decoded_payload = {
-        use base64::{Engine, engine::general_purpose};
-        general_purpose::STANDARD.decode(&received_messages[0].payload)
-            .expect("Failed to decode message payload")
-    };
-    let payload_text = String::from_utf8(decoded_payload)
-        .expect("Failed to convert payload to string");
-    
-    assert_eq!(payload_text, "Inter-node communication works!", 
-              "Message content should match");
+    println!("Relay verification: {:?}", relay);
+    assert!(relay.any(), "Messages should relay in at least one direction between node1 and node2");
 
     // Cleanup
     framework.cleanup_node(&node1).await.expect("Failed to cleanup node1");
@@ -231,4 +171,47 @@ async fn test_suite_2_inter_node_communication() {
     framework.cleanup_network().await.expect("Failed to cleanup network");
     
     println!("✅ Test Suite 2: Inter-Node Communication - PASSED");
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_suite_3_rest_over_docker_exec() {
+    // Initialize tracing (ignore if already initialized)
+    let _ = tracing_subscriber::fmt::try_init();
+
+    // Same isolation as the other suites, but every REST call runs inside the node container
+    let mut options = WakuTestFramework::isolated()
+        .expect("Failed to create test framework")
+        .options()
+        .clone();
+    options.rest_access = RestAccess::DockerExec;
+    let framework = WakuTestFramework::with_options(options)
+        .expect("Failed to create docker exec framework");
+
+    let node = framework.start_waku_node(WakuNodeConfig::default())
+        .await
+        .expect("Failed to start Waku node");
+
+    let node_info = framework.get_node_info(&node)
+        .await
+        .expect("Failed to get node info over docker exec");
+    assert!(!node_info.enr_uri.is_empty(), "ENR URI should not be empty");
+
+    framework.subscribe_to_topic(&node, TEST_TOPIC)
+        .await
+        .expect("Failed to subscribe over docker exec");
+
+    let message = create_test_message(TEST_MESSAGE, TEST_TOPIC);
+    framework.publish_message(&node, &message)
+        .await
+        .expect("Failed to publish over docker exec");
+
+    let received = framework.wait_for_message(&node, TEST_TOPIC, Duration::from_secs(10), |m| m.payload == message.payload)
+        .await
+        .expect("Failed to get messages over docker exec");
+    assert!(received.is_some(), "Should receive the published message over docker exec");
+
+    // Cleanup
+    framework.cleanup_node(&node).await.expect("Failed to cleanup node");
+
+    println!("✅ Test Suite 3: REST over Docker Exec - PASSED");
+}